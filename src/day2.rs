@@ -23,16 +23,15 @@ pub fn part1(input: &str) -> u32 {
 }
 
 fn count_diff(s1: &str, s2: &str) -> bool {
-    match s1.chars().zip(s2.chars()).try_fold(0, |acc, (a, b)| {
+    let diff = s1.chars().zip(s2.chars()).try_fold(0, |acc, (a, b)| {
         if (acc + (a != b) as u8) > 1 {
             None
         } else {
             Some(acc + (a != b) as u8)
         }
-    }) {
-        Some(1) => true,
-        _ => false,
-    }
+    });
+
+    diff == Some(1)
 }
 
 fn filter_diff(s1: &str, s2: &str) -> String {
@@ -5,6 +5,9 @@ use itertools::Itertools;
 use lazy_static::lazy_static;
 use ndarray::{s, Array1, Array2};
 use regex::Regex;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::{self, Write};
 
 lazy_static! {
     static ref PARTIAL_EVENT_RE: Regex =
@@ -17,46 +20,132 @@ lazy_static! {
     .unwrap();
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum EventType {
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum EventType {
     WakeUp,
     Asleep,
 }
 
-#[derive(Debug, Copy, Clone)]
+// matches the variant names serde uses, so the CSV and JSON exports agree
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            EventType::WakeUp => "WakeUp",
+            EventType::Asleep => "Asleep",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
 pub struct Event {
-    datetime: NaiveDateTime,
-    guard_id: usize,
-    event_type: EventType,
+    pub datetime: NaiveDateTime,
+    pub guard_id: usize,
+    pub event_type: EventType,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct SleepPeriod {
+    pub guard_id: usize,
+    pub start: NaiveDateTime,
+    /// Whole minutes asleep, rounded down, which is how the puzzle counts them.
+    pub minutes: i64,
+    /// The exact time asleep, for logs with seconds in their timestamps.
+    pub seconds: i64,
+}
+
+/// Timestamp formats accepted in the log, tried in order.
+///
+/// Timestamps carrying a UTC offset are converted into `timezone`, those without one
+/// are assumed to already be in it, so every event ends up on the same clock.
+#[derive(Debug, Clone)]
+pub struct TimestampFormats {
+    pub formats: Vec<String>,
+    pub timezone: FixedOffset,
+}
+
+impl Default for TimestampFormats {
+    fn default() -> Self {
+        let formats = [
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%d %H:%M%#z",
+            "%Y-%m-%d %H:%M:%S%#z",
+            "%Y-%m-%dT%H:%M%#z",
+            "%Y-%m-%dT%H:%M:%S%#z",
+        ];
+
+        TimestampFormats {
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+            timezone: FixedOffset::east_opt(0).unwrap(),
+        }
+    }
+}
+
+impl TimestampFormats {
+    fn parse(&self, s: &str) -> Option<NaiveDateTime> {
+        self.formats.iter().find_map(|f| {
+            DateTime::parse_from_str(s, f)
+                .map(|dt| dt.with_timezone(&self.timezone).naive_local())
+                .or_else(|_| NaiveDateTime::parse_from_str(s, f))
+                .ok()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampError {
+    pub line: usize,
+    pub timestamp: String,
 }
 
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: unrecognised timestamp `{}`", self.line, self.timestamp)
+    }
+}
+
+impl Error for TimestampError {}
+
 #[aoc_generator(day4)]
-pub fn parse_input(input: &str) -> Vec<Event> {
+pub fn parse_input(input: &str) -> Result<Vec<Event>, TimestampError> {
+    parse_input_with(input, &TimestampFormats::default())
+}
+
+pub fn parse_input_with(
+    input: &str,
+    formats: &TimestampFormats,
+) -> Result<Vec<Event>, TimestampError> {
     #[derive(Debug)]
     struct PartialEvent<'a> {
         datetime: NaiveDateTime,
         event: &'a str,
     }
 
-    let mut events: Vec<_> = input
+    let mut events = input
         .lines()
-        .filter_map(|l| {
+        .enumerate()
+        .filter_map(|(idx, l)| {
             let caps = PARTIAL_EVENT_RE.captures(l)?;
-
-            Some(PartialEvent {
-                datetime: NaiveDateTime::parse_from_str(
-                    caps.name("datetime")?.as_str(),
-                    "%Y-%m-%d %H:%M",
-                )
-                .ok()?,
-                event: caps.name("event")?.as_str(),
+            let timestamp = caps.name("datetime")?.as_str();
+
+            Some(match formats.parse(timestamp) {
+                Some(datetime) => Ok(PartialEvent {
+                    datetime,
+                    event: caps.name("event")?.as_str(),
+                }),
+                None => Err(TimestampError {
+                    line: idx + 1,
+                    timestamp: timestamp.to_owned(),
+                }),
             })
         })
-        .collect();
+        .collect::<Result<Vec<_>, _>>()?;
 
     events.sort_by_key(|e| e.datetime);
 
-    events
+    Ok(events
         .iter()
         .scan(None, |current_guard, l| {
             let caps = EVENT_RE.captures(l.event)?;
@@ -77,30 +166,71 @@ pub fn parse_input(input: &str) -> Vec<Event> {
                 event_type,
             }))
         })
-        .filter_map(|i| i)
-        .collect()
+        .flatten()
+        .collect())
 }
 
+// (guard, fell asleep, woke up)
 fn as_sleep_periods<'a>(
     inp: &'a [Event],
-) -> impl Iterator<Item = (usize, NaiveDateTime, i64)> + 'a {
+) -> impl Iterator<Item = (usize, NaiveDateTime, NaiveDateTime)> + 'a {
     inp.iter().tuples().map(|(l, r)| {
         assert_eq!(l.guard_id, r.guard_id);
         assert_eq!(l.event_type, EventType::Asleep);
         assert_eq!(r.event_type, EventType::WakeUp);
 
-        (
-            l.guard_id,
-            l.datetime,
-            (r.datetime - l.datetime).num_minutes(),
-        )
+        (l.guard_id, l.datetime, r.datetime)
     })
 }
 
+pub fn sleep_periods(inp: &[Event]) -> Vec<SleepPeriod> {
+    as_sleep_periods(inp)
+        .map(|(guard_id, start, end)| SleepPeriod {
+            guard_id,
+            start,
+            minutes: (end - start).num_minutes(),
+            seconds: (end - start).num_seconds(),
+        })
+        .collect()
+}
+
+pub fn events_to_csv(inp: &[Event]) -> String {
+    let mut out = String::from("datetime,guard_id,event_type\n");
+
+    for e in inp {
+        writeln!(out, "{},{},{}", e.datetime, e.guard_id, e.event_type).unwrap();
+    }
+
+    out
+}
+
+pub fn events_to_json(inp: &[Event]) -> serde_json::Result<String> {
+    serde_json::to_string(inp)
+}
+
+pub fn sleep_periods_to_csv(inp: &[Event]) -> String {
+    let mut out = String::from("guard_id,start,minutes,seconds\n");
+
+    for p in sleep_periods(inp) {
+        writeln!(
+            out,
+            "{},{},{},{}",
+            p.guard_id, p.start, p.minutes, p.seconds
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+pub fn sleep_periods_to_json(inp: &[Event]) -> serde_json::Result<String> {
+    serde_json::to_string(&sleep_periods(inp))
+}
+
 #[aoc(day4, part1)]
 pub fn part1(inp: &[Event]) -> usize {
     let guards_periods = as_sleep_periods(inp)
-        .map(|(id, s, d)| (id, (s, d)))
+        .map(|(id, s, e)| (id, (s, (e - s).num_minutes())))
         .into_group_map();
 
     let (most_asleep_guard, most_asleep_minute, _) = guards_periods.iter().fold(
@@ -130,7 +260,7 @@ pub fn part1(inp: &[Event]) -> usize {
         },
     );
 
-    return most_asleep_minute.unwrap() as usize * most_asleep_guard;
+    most_asleep_minute.unwrap() * most_asleep_guard
 }
 
 // returns maximum and second maximum
@@ -169,7 +299,7 @@ pub fn part2(inp: &[Event]) -> usize {
     use bimap::BiMap;
 
     let guards_periods = as_sleep_periods(inp)
-        .map(|(id, s, d)| (id, (s, d)))
+        .map(|(id, s, e)| (id, (s, (e - s).num_minutes())))
         .into_group_map();
 
     let guard_idx_ids: BiMap<_, _> = guards_periods.keys().enumerate().collect();
@@ -204,5 +334,123 @@ pub fn part2(inp: &[Event]) -> usize {
 
     let guard_id = guard_idx_ids.get_by_left(&max_idx).unwrap();
 
-    return *guard_id * col_idx;
+    *guard_id * col_idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        events_to_csv, events_to_json, parse_input, parse_input_with, sleep_periods,
+        sleep_periods_to_csv, sleep_periods_to_json, TimestampError, TimestampFormats,
+    };
+    use chrono::FixedOffset;
+
+    const INPUT: &str = r#"[1518-11-01 00:05] falls asleep
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:25] wakes up
+[1518-11-02 00:40] falls asleep
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:50] wakes up"#;
+
+    #[test]
+    fn export_csv() {
+        let parsed = parse_input(INPUT).unwrap();
+
+        assert_eq!(
+            events_to_csv(&parsed),
+            "datetime,guard_id,event_type
+1518-11-01 00:05:00,10,Asleep
+1518-11-01 00:25:00,10,WakeUp
+1518-11-02 00:40:00,99,Asleep
+1518-11-02 00:50:00,99,WakeUp
+"
+        );
+
+        assert_eq!(
+            sleep_periods_to_csv(&parsed),
+            "guard_id,start,minutes,seconds
+10,1518-11-01 00:05:00,20,1200
+99,1518-11-02 00:40:00,10,600
+"
+        );
+    }
+
+    #[test]
+    fn export_json() {
+        let parsed = parse_input(INPUT).unwrap();
+
+        assert_eq!(
+            events_to_json(&parsed).unwrap(),
+            r#"[{"datetime":"1518-11-01T00:05:00","guard_id":10,"event_type":"Asleep"},{"datetime":"1518-11-01T00:25:00","guard_id":10,"event_type":"WakeUp"},{"datetime":"1518-11-02T00:40:00","guard_id":99,"event_type":"Asleep"},{"datetime":"1518-11-02T00:50:00","guard_id":99,"event_type":"WakeUp"}]"#
+        );
+
+        assert_eq!(
+            sleep_periods_to_json(&parsed).unwrap(),
+            r#"[{"guard_id":10,"start":"1518-11-01T00:05:00","minutes":20,"seconds":1200},{"guard_id":99,"start":"1518-11-02T00:40:00","minutes":10,"seconds":600}]"#
+        );
+    }
+
+    #[test]
+    fn mixed_timestamps() {
+        let inp = r#"[1518-11-01T00:25:30] wakes up
+[1518-11-01 09:05+09:00] falls asleep
+[1518-11-01T00:00:00Z] Guard #10 begins shift"#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        assert_eq!(
+            sleep_periods_to_csv(&parsed),
+            "guard_id,start,minutes,seconds
+10,1518-11-01 00:05:00,20,1230
+"
+        );
+    }
+
+    #[test]
+    fn target_timezone() {
+        let formats = TimestampFormats {
+            timezone: FixedOffset::east_opt(3600).unwrap(),
+            ..TimestampFormats::default()
+        };
+
+        let inp = r#"[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01T00:05:00+01:00] falls asleep
+[1518-10-31 23:25-00:00] wakes up"#;
+
+        let parsed = parse_input_with(inp, &formats).unwrap();
+
+        assert_eq!(
+            sleep_periods_to_csv(&parsed),
+            "guard_id,start,minutes,seconds
+10,1518-11-01 00:05:00,20,1200
+"
+        );
+    }
+
+    #[test]
+    fn unrecognised_timestamp() {
+        let inp = r#"[1518-11-01 00:00] Guard #10 begins shift
+[11/01/1518 00:05] falls asleep"#;
+
+        assert_eq!(
+            parse_input(inp).unwrap_err(),
+            TimestampError {
+                line: 2,
+                timestamp: "11/01/1518 00:05".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn partial_minutes() {
+        let inp = r#"[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01T00:05:30] falls asleep
+[1518-11-01T00:25:10] wakes up"#;
+
+        let periods = sleep_periods(&parse_input(inp).unwrap());
+
+        // 19 minutes 40 seconds, rounded down to 19 whole minutes
+        assert_eq!(periods[0].seconds, 1180);
+        assert_eq!(periods[0].minutes, 19);
+    }
 }
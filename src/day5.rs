@@ -3,7 +3,12 @@ use aoc_runner_derive::aoc;
 use itertools::Itertools;
 use rayon::prelude::*;
 use ropey::Rope;
-use hashbrown::HashSet;
+use serde::Serialize;
+use hashbrown::{HashMap, HashSet};
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+use std::{fmt, fs, io, str};
 
 fn fix<T: Clone>(mut val: T, fun: impl Fn(&T) -> Option<T>) -> T {
     loop {
@@ -55,13 +60,286 @@ fn step_remove(poly: &Rope) -> Option<Rope> {
     }
 }
 
+/// Decides whether two adjacent units annihilate each other.
+pub trait ReactionRule {
+    type Unit: Clone;
+
+    fn reacts(&self, a: &Self::Unit, b: &Self::Unit) -> bool;
+}
+
+/// The puzzle's rule: the same ASCII letter in opposite cases.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AsciiCase;
+
+impl ReactionRule for AsciiCase {
+    type Unit = char;
+
+    fn reacts(&self, a: &char, b: &char) -> bool {
+        is_match(*a, *b)
+    }
+}
+
+/// The same letter in opposite cases under Unicode case folding, so `Ä` reacts with `ä`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UnicodeCase;
+
+impl ReactionRule for UnicodeCase {
+    type Unit = char;
+
+    fn reacts(&self, a: &char, b: &char) -> bool {
+        ((a.is_uppercase() && b.is_lowercase()) || (a.is_lowercase() && b.is_uppercase()))
+            && a.to_lowercase().eq(b.to_lowercase())
+    }
+}
+
+/// An explicit, symmetric table of reacting pairs. Units are arbitrary tokens, see `tokens`.
+#[derive(Debug, Clone, Default)]
+pub struct PairTable {
+    pairs: HashMap<String, HashSet<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairTableError {
+    pub line: usize,
+}
+
+impl fmt::Display for PairTableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: expected two whitespace separated units",
+            self.line
+        )
+    }
+}
+
+impl Error for PairTableError {}
+
+impl PairTable {
+    pub fn insert(&mut self, a: &str, b: &str) {
+        self.pairs
+            .entry(a.to_owned())
+            .or_default()
+            .insert(b.to_owned());
+        self.pairs
+            .entry(b.to_owned())
+            .or_default()
+            .insert(a.to_owned());
+    }
+
+    /// Parses one pair per line, e.g. `Fe Cl`. Blank lines are skipped.
+    pub fn parse(table: &str) -> Result<PairTable, PairTableError> {
+        let mut pairs = PairTable::default();
+
+        for (idx, line) in table.lines().enumerate() {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => {}
+                [a, b] => pairs.insert(a, b),
+                _ => return Err(PairTableError { line: idx + 1 }),
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<PairTable> {
+        PairTable::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl ReactionRule for PairTable {
+    type Unit = String;
+
+    fn reacts(&self, a: &String, b: &String) -> bool {
+        self.pairs.get(a).is_some_and(|r| r.contains(b))
+    }
+}
+
+/// Splits a polymer written as whitespace separated tokens into units.
+pub fn tokens<'a>(input: &'a str) -> impl Iterator<Item = String> + 'a {
+    input.split_whitespace().map(|t| t.to_owned())
+}
+
+// pushes each unit onto the already reduced stack, annihilating with the top as we go
+fn react_onto<R: ReactionRule>(
+    rule: &R,
+    stack: &mut Vec<R::Unit>,
+    units: impl IntoIterator<Item = R::Unit>,
+) {
+    for unit in units {
+        match stack.last() {
+            Some(top) if rule.reacts(top, &unit) => {
+                stack.pop();
+            }
+            _ => stack.push(unit),
+        }
+    }
+}
+
+pub fn reduce<R: ReactionRule>(rule: &R, units: impl IntoIterator<Item = R::Unit>) -> Vec<R::Unit> {
+    let units = units.into_iter();
+    let mut stack = Vec::with_capacity(units.size_hint().0);
+    react_onto(rule, &mut stack, units);
+    stack
+}
+
+// reduce(a ++ b) == reduce(reduce(a) ++ reduce(b)), so chunks can be reduced
+// independently and only their boundaries need reacting when merged
+pub fn reduce_parallel<R>(rule: &R, units: &[R::Unit], chunk_size: usize) -> Vec<R::Unit>
+where
+    R: ReactionRule + Sync,
+    R::Unit: Send + Sync,
+{
+    units
+        .par_chunks(chunk_size)
+        .map(|chunk| reduce(rule, chunk.iter().cloned()))
+        .reduce(Vec::new, |mut left, right| {
+            react_onto(rule, &mut left, right);
+            left
+        })
+}
+
+/// Reduces a polymer as it's read, so memory use is bounded by the survivors rather than
+/// the input. Whitespace, such as a trailing newline, is skipped.
+pub fn reduce_reader<R, T>(rule: &R, mut reader: T) -> io::Result<Vec<char>>
+where
+    R: ReactionRule<Unit = char>,
+    T: Read,
+{
+    let mut stack = Vec::new();
+    let mut buf = [0u8; 8192];
+    // bytes of a UTF-8 sequence that was split between reads
+    let mut pending = 0;
+
+    loop {
+        let filled = match reader.read(&mut buf[pending..]) {
+            Ok(0) => break,
+            Ok(n) => pending + n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let valid = match str::from_utf8(&buf[..filled]) {
+            Ok(s) => s,
+            Err(e) if e.error_len().is_none() => str::from_utf8(&buf[..e.valid_up_to()]).unwrap(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let consumed = valid.len();
+
+        react_onto(
+            rule,
+            &mut stack,
+            valid.chars().filter(|c| !c.is_whitespace()),
+        );
+
+        buf.copy_within(consumed..filled, 0);
+        pending = filled - consumed;
+    }
+
+    if pending > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "polymer ends partway through a UTF-8 sequence",
+        ));
+    }
+
+    Ok(stack)
+}
+
+pub fn reduced_len_reader<R, T>(rule: &R, reader: T) -> io::Result<usize>
+where
+    R: ReactionRule<Unit = char>,
+    T: Read,
+{
+    reduce_reader(rule, reader).map(|s| s.len())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Positioned<U> {
+    /// Index of the unit in the original input.
+    pub offset: usize,
+    pub unit: U,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Annihilation<U> {
+    pub left: Positioned<U>,
+    pub right: Positioned<U>,
+    /// How deeply nested the cascade that brought the pair together was, 0 for units
+    /// that were already adjacent in the input.
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReactionTrace<U> {
+    /// In the order the reactions happened.
+    pub annihilations: Vec<Annihilation<U>>,
+    pub survivors: Vec<Positioned<U>>,
+}
+
+impl<U: Serialize> ReactionTrace<U> {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Same reduction as `reduce`, but keeps a record of what reacted where.
+pub fn reduce_traced<R: ReactionRule>(
+    rule: &R,
+    units: impl IntoIterator<Item = R::Unit>,
+) -> ReactionTrace<R::Unit> {
+    // alongside each survivor we keep the deepest cascade cleared out just after it
+    let mut stack: Vec<(Positioned<R::Unit>, usize)> = Vec::new();
+    let mut annihilations = Vec::new();
+
+    for (offset, unit) in units.into_iter().enumerate() {
+        match stack.last() {
+            Some((top, _)) if rule.reacts(&top.unit, &unit) => {
+                let (left, depth) = stack.pop().unwrap();
+
+                if let Some((_, cleared)) = stack.last_mut() {
+                    *cleared = (*cleared).max(depth + 1);
+                }
+
+                annihilations.push(Annihilation {
+                    left,
+                    right: Positioned { offset, unit },
+                    depth,
+                });
+            }
+            _ => stack.push((Positioned { offset, unit }, 0)),
+        }
+    }
+
+    ReactionTrace {
+        annihilations,
+        survivors: stack.into_iter().map(|(p, _)| p).collect(),
+    }
+}
+
+fn reduce_stack(input: &str) -> Vec<char> {
+    reduce(&AsciiCase, input.chars())
+}
+
 #[aoc(day5, part1)]
 pub fn part1(input: &str) -> usize {
     fix(Rope::from_str(input), step_remove).len_chars()
 }
 
-#[aoc(day5, part2)]
-pub fn part2(input: &str) -> usize {
+#[aoc(day5, part1, Stack)]
+pub fn part1_stack(input: &str) -> usize {
+    reduce_stack(input).len()
+}
+
+#[aoc(day5, part1, Parallel)]
+pub fn part1_parallel(input: &str) -> usize {
+    let units: Vec<char> = input.chars().collect();
+
+    reduce_parallel(&AsciiCase, &units, 1 << 16).len()
+}
+
+fn solve_part2(input: &str, reduced_len: impl Fn(&str) -> usize + Sync) -> usize {
     let units: HashSet<_> = input.chars().map(|c| c.to_ascii_lowercase()).collect();
 
     units
@@ -73,8 +351,191 @@ pub fn part2(input: &str) -> usize {
                 .filter(|ic| ic.to_ascii_lowercase() != c)
                 .collect();
 
-            fix(Rope::from_str(&filtered), step_remove).len_chars()
+            reduced_len(&filtered)
         })
         .min()
         .unwrap()
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UnitRemoval {
+    pub unit: char,
+    pub length: usize,
+}
+
+/// Every unit type in the polymer with the reduced length left after removing it,
+/// shortest first.
+pub fn removal_ranking(input: &str) -> Vec<UnitRemoval> {
+    // removing a unit commutes with reacting, so each candidate can start from the
+    // part 1 residue instead of the whole input
+    let residue = reduce_stack(input);
+    let units: HashSet<_> = input.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    let mut ranking: Vec<_> = units
+        .into_iter()
+        .par_bridge()
+        .map(|unit| {
+            let remaining = residue
+                .iter()
+                .cloned()
+                .filter(|c| c.to_ascii_lowercase() != unit);
+
+            UnitRemoval {
+                unit,
+                length: reduce(&AsciiCase, remaining).len(),
+            }
+        })
+        .collect();
+
+    ranking.sort_by_key(|r| (r.length, r.unit));
+    ranking
+}
+
+#[aoc(day5, part2)]
+pub fn part2(input: &str) -> usize {
+    solve_part2(input, part1)
+}
+
+#[aoc(day5, part2, Stack)]
+pub fn part2_stack(input: &str) -> usize {
+    removal_ranking(input)[0].length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        part1, part1_parallel, part1_stack, part2, part2_stack, reduce, reduce_parallel,
+        reduce_reader, reduce_stack, reduce_traced, reduced_len_reader, removal_ranking, tokens,
+        AsciiCase, PairTable, PairTableError, UnicodeCase, UnitRemoval,
+    };
+    use std::io::{self, Read};
+
+    const INPUT: &str = "dabAcCaCBAcCcaDA";
+
+    #[test]
+    fn t1() {
+        assert_eq!(part1(INPUT), 10);
+        assert_eq!(part1_stack(INPUT), 10);
+        assert_eq!(part1_parallel(INPUT), 10);
+    }
+
+    #[test]
+    fn parallel_boundaries() {
+        // cascades that span many chunks
+        let nested: String = "abcdefghij"
+            .repeat(50)
+            .chars()
+            .chain("JIHGFEDCBA".repeat(50).chars())
+            .collect();
+
+        let nested: Vec<char> = nested.chars().collect();
+        let units: Vec<char> = INPUT.chars().collect();
+
+        for chunk_size in 1..12 {
+            assert!(reduce_parallel(&AsciiCase, &nested, chunk_size).is_empty());
+            assert_eq!(
+                reduce_parallel(&AsciiCase, &units, chunk_size),
+                reduce_stack(INPUT)
+            );
+        }
+    }
+
+    #[test]
+    fn t2() {
+        assert_eq!(part2(INPUT), 4);
+        assert_eq!(part2_stack(INPUT), 4);
+    }
+
+    #[test]
+    fn ranking() {
+        let ranking: Vec<_> = removal_ranking(INPUT)
+            .into_iter()
+            .map(|UnitRemoval { unit, length }| (unit, length))
+            .collect();
+
+        assert_eq!(ranking, vec![('c', 4), ('a', 6), ('d', 6), ('b', 8)]);
+    }
+
+    #[test]
+    fn unicode_rule() {
+        assert!(reduce(&UnicodeCase, "ÄbBäÉé".chars()).is_empty());
+        assert_eq!(reduce(&AsciiCase, "ÄbBä".chars()), vec!['Ä', 'ä']);
+        assert_eq!(reduce(&UnicodeCase, INPUT.chars()), reduce_stack(INPUT));
+    }
+
+    #[test]
+    fn pair_table() {
+        let table = PairTable::parse("Fe Cl\n\nNa OH\n").unwrap();
+
+        assert!(reduce(&table, tokens("Na Fe Cl OH")).is_empty());
+        assert_eq!(reduce(&table, tokens("Cl Fe Fe OH")), vec!["Fe", "OH"]);
+
+        assert_eq!(
+            PairTable::parse("Fe Cl\nNa").unwrap_err(),
+            PairTableError { line: 2 }
+        );
+    }
+
+    #[test]
+    fn trace() {
+        let trace = reduce_traced(&AsciiCase, INPUT.chars());
+
+        let annihilations: Vec<_> = trace
+            .annihilations
+            .iter()
+            .map(|a| (a.left.offset, a.right.offset, a.depth))
+            .collect();
+        let survivors: String = trace.survivors.iter().map(|s| s.unit).collect();
+        let offsets: Vec<_> = trace.survivors.iter().map(|s| s.offset).collect();
+
+        assert_eq!(annihilations, vec![(4, 5, 0), (3, 6, 1), (10, 11, 0)]);
+        assert_eq!(survivors, "dabCBAcaDA");
+        assert_eq!(offsets, vec![0, 1, 2, 7, 8, 9, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn trace_json() {
+        let trace = reduce_traced(&AsciiCase, "xabBA".chars());
+
+        assert_eq!(
+            trace.to_json().unwrap(),
+            concat!(
+                r#"{"annihilations":["#,
+                r#"{"left":{"offset":2,"unit":"b"},"right":{"offset":3,"unit":"B"},"depth":0},"#,
+                r#"{"left":{"offset":1,"unit":"a"},"right":{"offset":4,"unit":"A"},"depth":1}],"#,
+                r#""survivors":[{"offset":0,"unit":"x"}]}"#
+            )
+        );
+    }
+
+    // hands out a few bytes per read so chunks split UTF-8 sequences
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn reader() {
+        let inp = format!("{}\n", INPUT);
+
+        assert_eq!(
+            reduce_reader(&AsciiCase, Trickle(inp.as_bytes())).unwrap(),
+            reduce_stack(INPUT)
+        );
+        assert_eq!(reduced_len_reader(&AsciiCase, inp.as_bytes()).unwrap(), 10);
+
+        assert_eq!(
+            reduce_reader(&UnicodeCase, Trickle("xÄéÉbBäy".as_bytes())).unwrap(),
+            vec!['x', 'y']
+        );
+
+        let truncated = &"aÄ".as_bytes()[..2];
+        assert!(reduce_reader(&AsciiCase, truncated).is_err());
+    }
+}
@@ -1,135 +1,1876 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use cgmath::Point2;
+use cgmath::{Point2, Point3};
+use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
-use lazy_static::lazy_static;
-use regex::Regex;
-use spade::{rtree::RTree, BoundingRect, SpatialObject};
-use hashbrown::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::iter;
+
+/// A point on an integer grid with `DIM` axes.
+pub trait Coords: Copy + PartialEq + fmt::Debug {
+    const DIM: usize;
+
+    fn coord(&self, axis: usize) -> i32;
+
+    fn from_coords(f: impl FnMut(usize) -> i32) -> Self;
+}
+
+impl Coords for Point2<i32> {
+    const DIM: usize = 2;
+
+    fn coord(&self, axis: usize) -> i32 {
+        if axis == 0 {
+            self.x
+        } else {
+            self.y
+        }
+    }
+
+    fn from_coords(mut f: impl FnMut(usize) -> i32) -> Self {
+        Point2::new(f(0), f(1))
+    }
+}
+
+impl Coords for Point3<i32> {
+    const DIM: usize = 3;
+
+    fn coord(&self, axis: usize) -> i32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    fn from_coords(mut f: impl FnMut(usize) -> i32) -> Self {
+        Point3::new(f(0), f(1), f(2))
+    }
+}
+
+impl<const N: usize> Coords for [i32; N] {
+    const DIM: usize = N;
+
+    fn coord(&self, axis: usize) -> i32 {
+        self[axis]
+    }
+
+    fn from_coords(f: impl FnMut(usize) -> i32) -> Self {
+        std::array::from_fn(f)
+    }
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-pub struct TaxicabSpatial(pub Point2<i32>);
+pub struct TaxicabSpatial<P = Point2<i32>>(pub P);
+
+impl TaxicabSpatial {
+    pub fn new(x: i32, y: i32) -> TaxicabSpatial {
+        TaxicabSpatial(Point2::new(x, y))
+    }
+}
+
+// widened before subtracting, as points at opposite ends of i32 are further apart
+// than i32 can hold
+fn axis_diffs<P: Coords>(a: P, b: P) -> impl Iterator<Item = i64> {
+    (0..P::DIM).map(move |axis| (i64::from(a.coord(axis)) - i64::from(b.coord(axis))).abs())
+}
+
+/// How far apart two grid points are when assigning cells to their closest point.
+pub trait Metric {
+    fn distance<P: Coords>(&self, a: P, b: P) -> i64;
+
+    /// The smallest possible distance between two points `d` apart along one axis.
+    fn axis_bound(&self, d: i64) -> i64;
+}
+
+/// L1, the puzzle's metric.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Taxicab;
+
+impl Metric for Taxicab {
+    fn distance<P: Coords>(&self, a: P, b: P) -> i64 {
+        axis_diffs(a, b).sum()
+    }
+
+    fn axis_bound(&self, d: i64) -> i64 {
+        d.abs()
+    }
+}
+
+/// L∞, the number of king moves between two points.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance<P: Coords>(&self, a: P, b: P) -> i64 {
+        axis_diffs(a, b).max().unwrap_or(0)
+    }
+
+    fn axis_bound(&self, d: i64) -> i64 {
+        d.abs()
+    }
+}
+
+/// Squared L2, which orders points the same as Euclidean distance without the roots.
+///
+/// The squares are summed in i64, so points should be within about two billion of each
+/// other along every axis, less in more than two dimensions.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SquaredEuclidean;
+
+impl Metric for SquaredEuclidean {
+    fn distance<P: Coords>(&self, a: P, b: P) -> i64 {
+        axis_diffs(a, b).map(|d| d * d).sum()
+    }
+
+    fn axis_bound(&self, d: i64) -> i64 {
+        d * d
+    }
+}
+
+/// A metric whose regions can be sorted into finite and infinite ones, along with a box
+/// big enough to hold every finite region whole.
+pub trait RegionMetric<P: Coords>: Metric {
+    /// The box to scan, holding the points and every finite region with at least a cell
+    /// to spare on every side.
+    fn region_box(&self, points: &[TaxicabSpatial<P>]) -> (P, P);
+
+    /// Whether each point's region goes on forever, given the owner of each cell of
+    /// `region_box` in the order `box_cells` visits them. By default that's the regions
+    /// reaching a face of the box.
+    fn infinite_regions(
+        &self,
+        points: &[TaxicabSpatial<P>],
+        (lo, hi): (P, P),
+        owners: &[Option<usize>],
+    ) -> Vec<bool> {
+        let mut infinite = vec![false; points.len()];
+
+        for (cell, owner) in box_cells(lo, hi).zip(owners) {
+            let on_face = (0..P::DIM).any(|axis| {
+                cell.coord(axis) == lo.coord(axis) || cell.coord(axis) == hi.coord(axis)
+            });
+
+            if let (true, Some(n)) = (on_face, owner) {
+                infinite[*n] = true;
+            }
+        }
+
+        infinite
+    }
+}
+
+impl<P: Coords> RegionMetric<P> for Taxicab {
+    // the owner of a cell just outside the bounding box is also closest to the next cell
+    // out, as that step moves it one further from every point
+    fn region_box(&self, points: &[TaxicabSpatial<P>]) -> (P, P) {
+        grow_box(bounding_box(points), 1)
+    }
+}
+
+impl<P: Coords> RegionMetric<P> for Chebyshev {
+    // once a cell is as far past one side of the bounding box as the box is wide along
+    // any axis, stepping it outward along that axis, and along any other it is already
+    // outside of, adds one to its distance to every point. So its owner keeps it forever,
+    // and a finite region can't get that far out.
+    fn region_box(&self, points: &[TaxicabSpatial<P>]) -> (P, P) {
+        let (lo, hi) = bounding_box(points);
+
+        let widest = (0..P::DIM)
+            .map(|axis| i64::from(hi.coord(axis)) - i64::from(lo.coord(axis)))
+            .max()
+            .unwrap_or(0);
+
+        grow_box((lo, hi), widest.max(1).min(i64::from(i32::MAX)) as i32)
+    }
+}
+
+/// In the plane only. A finite region can reach well past the points here, e.g. among
+/// `0, 0`, `10, 0`, `5, 1` and `5, 10` the one around `5, 1` runs down to `5, -11`, so
+/// the box is grown to fit each finite region as measured a row at a time.
+impl RegionMetric<Point2<i32>> for SquaredEuclidean {
+    fn region_box(&self, points: &[TaxicabSpatial]) -> (Point2<i32>, Point2<i32>) {
+        let (lo, hi) = (0..points.len())
+            .filter(|&site| !on_hull(points, site))
+            .filter_map(|site| euclidean_bounds(points, site))
+            .fold(bounding_box(points), |(lo, hi), (rlo, rhi)| {
+                (
+                    Point2::new(lo.x.min(rlo.x), lo.y.min(rlo.y)),
+                    Point2::new(hi.x.max(rhi.x), hi.y.max(rhi.y)),
+                )
+            });
+
+        grow_box((lo, hi), 1)
+    }
+
+    fn infinite_regions(
+        &self,
+        points: &[TaxicabSpatial],
+        _box: (Point2<i32>, Point2<i32>),
+        _owners: &[Option<usize>],
+    ) -> Vec<bool> {
+        (0..points.len())
+            .map(|site| on_hull(points, site))
+            .collect()
+    }
+}
+
+// whether the L2 region around `points[site]` goes on forever, which it does when every
+// other point lies in one closed half-plane through it: heading straight out of that
+// half-plane gets relatively closer to it than to all of them. A duplicated point owns
+// nothing at all.
+fn on_hull(points: &[TaxicabSpatial], site: usize) -> bool {
+    let p = points[site].0;
+
+    let dirs: Vec<_> = points
+        .iter()
+        .enumerate()
+        .filter(|&(idx, _)| idx != site)
+        .map(|(_, q)| {
+            (
+                i128::from(q.0.x) - i128::from(p.x),
+                i128::from(q.0.y) - i128::from(p.y),
+            )
+        })
+        .collect();
+
+    if dirs.contains(&(0, 0)) {
+        return false;
+    }
+
+    // if the points fit in a half-plane, it can be turned until one of them is on its edge
+    dirs.is_empty()
+        || dirs.iter().any(|&(ax, ay)| {
+            let sides: Vec<_> = dirs
+                .iter()
+                .map(|&(bx, by)| (ax * by - ay * bx).signum())
+                .collect();
+
+            sides.iter().all(|&s| s >= 0) || sides.iter().all(|&s| s <= 0)
+        })
+}
+
+// the open interval of x, as fractions over positive denominators, where cells in row `y`
+// are strictly closer to `points[site]` than to any other point under L2. `None` when the
+// row misses the region, or the region is unbounded along it.
+fn euclidean_row(
+    points: &[TaxicabSpatial],
+    site: usize,
+    y: i128,
+) -> Option<((i128, i128), (i128, i128))> {
+    let (px, py) = (i128::from(points[site].0.x), i128::from(points[site].0.y));
+
+    let mut lower: Option<(i128, i128)> = None;
+    let mut upper: Option<(i128, i128)> = None;
+
+    for (idx, q) in points.iter().enumerate() {
+        if idx == site {
+            continue;
+        }
+
+        let (qx, qy) = (i128::from(q.0.x), i128::from(q.0.y));
+
+        // |c - p|² < |c - q|² is linear in c: 2 (q - p) · c < |q|² - |p|²
+        let a = 2 * (qx - px);
+        let rest = qx * qx + qy * qy - px * px - py * py - 2 * (qy - py) * y;
+
+        match a.signum() {
+            1 if upper.is_none_or(|(n, d)| rest * d < n * a) => upper = Some((rest, a)),
+            -1 if lower.is_none_or(|(n, d)| -rest * d > n * -a) => lower = Some((-rest, -a)),
+            0 if rest <= 0 => return None,
+            _ => {}
+        }
+    }
+
+    let (lower, upper) = (lower?, upper?);
+
+    if lower.0 * upper.1 < upper.0 * lower.1 {
+        Some((lower, upper))
+    } else {
+        None
+    }
+}
+
+// the lowest and highest corners of the cells in a finite L2 region. The region is an
+// open convex polygon, so the rows it crosses run on from the point's own without gaps.
+fn euclidean_bounds(points: &[TaxicabSpatial], site: usize) -> Option<(Point2<i32>, Point2<i32>)> {
+    let to_i32 = |v: i128| v.max(i128::from(i32::MIN)).min(i128::from(i32::MAX)) as i32;
+    let mut bounds: Option<(Point2<i32>, Point2<i32>)> = None;
+
+    for &(start, step) in &[(0, 1), (-1, -1)] {
+        let mut y = i128::from(points[site].0.y) + start;
+
+        while let Some(((ln, ld), (un, ud))) = euclidean_row(points, site, y) {
+            // the integers strictly inside the interval
+            let first = to_i32(ln.div_euclid(ld) + 1);
+            let last = to_i32(-(-un).div_euclid(ud) - 1);
+
+            if first <= last {
+                let y = to_i32(y);
+                let (lo, hi) = bounds.unwrap_or((Point2::new(first, y), Point2::new(last, y)));
+
+                bounds = Some((
+                    Point2::new(lo.x.min(first), lo.y.min(y)),
+                    Point2::new(hi.x.max(last), hi.y.max(y)),
+                ));
+            }
+
+            y += step;
+        }
+    }
+
+    bounds
+}
+
+fn bounding_box<P: Coords>(points: &[TaxicabSpatial<P>]) -> (P, P) {
+    let bounds: Vec<_> = (0..P::DIM)
+        .map(|axis| {
+            points
+                .iter()
+                .map(|p| p.0.coord(axis))
+                .minmax()
+                .into_option()
+                .unwrap()
+        })
+        .collect();
+
+    (
+        P::from_coords(|axis| bounds[axis].0),
+        P::from_coords(|axis| bounds[axis].1),
+    )
+}
+
+// grows (or shrinks) a box by `by` cells on every side, stopping at the edges of i32
+fn grow_box<P: Coords>((lo, hi): (P, P), by: i32) -> (P, P) {
+    (
+        P::from_coords(|axis| lo.coord(axis).saturating_sub(by)),
+        P::from_coords(|axis| hi.coord(axis).saturating_add(by)),
+    )
+}
+
+// every cell from lo to hi inclusive, with the first axis changing fastest
+fn box_cells<P: Coords>(lo: P, hi: P) -> impl Iterator<Item = P> {
+    let mut next = if (0..P::DIM).all(|axis| lo.coord(axis) <= hi.coord(axis)) {
+        Some(lo)
+    } else {
+        None
+    };
+
+    iter::from_fn(move || {
+        let cell = next?;
+
+        next = (0..P::DIM)
+            .find(|&axis| cell.coord(axis) < hi.coord(axis))
+            .map(|step| {
+                P::from_coords(|axis| {
+                    if axis < step {
+                        lo.coord(axis)
+                    } else if axis == step {
+                        cell.coord(axis) + 1
+                    } else {
+                        cell.coord(axis)
+                    }
+                })
+            });
+
+        Some(cell)
+    })
+}
+
+#[derive(Debug)]
+struct KdNode<P> {
+    // position of the point in the slice the index was built from, or the id it was
+    // inserted under
+    id: usize,
+    point: TaxicabSpatial<P>,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+    // removed points stay in the tree to route searches until the next rebuild
+    removed: bool,
+}
+
+/// A k-d tree answering nearest neighbour queries exactly under `M`.
+///
+/// Pruning only relies on `Metric::axis_bound` for the distance to a splitting plane,
+/// which is a lower bound on the distance to anything on the far side of it.
+///
+/// Points can be inserted and removed after building. Inserts hang off a leaf and
+/// removals only mark their node, so the tree is rebuilt once it has doubled in size or
+/// more of it is removed than not.
+#[derive(Debug)]
+pub struct NearestIndex<M, P = Point2<i32>> {
+    metric: M,
+    nodes: Vec<KdNode<P>>,
+    root: Option<usize>,
+    // node of each id still in the index
+    positions: HashMap<usize, usize>,
+    removed: usize,
+    // nodes in the tree after the last rebuild
+    built: usize,
+}
+
+impl<M: Metric + Default, P: Coords> Default for NearestIndex<M, P> {
+    fn default() -> NearestIndex<M, P> {
+        NearestIndex::new(&[], M::default())
+    }
+}
+
+impl<M: Metric, P: Coords> NearestIndex<M, P> {
+    pub fn new(points: &[TaxicabSpatial<P>], metric: M) -> NearestIndex<M, P> {
+        let mut index = NearestIndex {
+            metric,
+            nodes: Vec::with_capacity(points.len()),
+            root: None,
+            positions: HashMap::with_capacity(points.len()),
+            removed: 0,
+            built: 0,
+        };
+
+        let mut points: Vec<_> = points.iter().cloned().enumerate().collect();
+
+        index.root = index.build(&mut points, 0);
+        index.built = index.nodes.len();
+        index
+    }
+
+    /// Adds a point under `id`, which shouldn't already be in use.
+    pub fn insert(&mut self, id: usize, point: TaxicabSpatial<P>) {
+        let mut parent = None;
+        let mut next = self.root;
+
+        while let Some(idx) = next {
+            let node = &self.nodes[idx];
+            let left = point.0.coord(node.axis) < node.point.0.coord(node.axis);
+
+            parent = Some((idx, left));
+            next = if left { node.left } else { node.right };
+        }
+
+        self.nodes.push(KdNode {
+            id,
+            point,
+            axis: parent.map_or(0, |(idx, _)| (self.nodes[idx].axis + 1) % P::DIM),
+            left: None,
+            right: None,
+            removed: false,
+        });
+
+        let node = Some(self.nodes.len() - 1);
+        self.positions.insert(id, self.nodes.len() - 1);
+
+        match parent {
+            Some((idx, true)) => self.nodes[idx].left = node,
+            Some((idx, false)) => self.nodes[idx].right = node,
+            None => self.root = node,
+        }
+
+        if self.nodes.len() > 2 * self.built {
+            self.rebuild();
+        }
+    }
+
+    /// Drops the point with the given id, returning whether there was one.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let idx = match self.positions.remove(&id) {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        self.nodes[idx].removed = true;
+        self.removed += 1;
+
+        if self.removed > self.nodes.len() - self.removed {
+            self.rebuild();
+        }
+
+        true
+    }
+
+    fn rebuild(&mut self) {
+        let mut points: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|node| !node.removed)
+            .map(|node| (node.id, node.point))
+            .collect();
+
+        self.nodes.clear();
+        self.positions.clear();
+        self.removed = 0;
+
+        self.root = self.build(&mut points, 0);
+        self.built = self.nodes.len();
+    }
+
+    fn build(&mut self, points: &mut [(usize, TaxicabSpatial<P>)], depth: usize) -> Option<usize> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let axis = depth % P::DIM;
+        points.sort_unstable_by_key(|(_, p)| p.0.coord(axis));
+
+        let (left, rest) = points.split_at_mut(points.len() / 2);
+        let (median, right) = rest.split_first_mut().unwrap();
+
+        let left = self.build(left, depth + 1);
+        let right = self.build(right, depth + 1);
+
+        let (id, point) = *median;
+
+        self.nodes.push(KdNode {
+            id,
+            point,
+            axis,
+            left,
+            right,
+            removed: false,
+        });
+
+        self.positions.insert(id, self.nodes.len() - 1);
+
+        Some(self.nodes.len() - 1)
+    }
+
+    /// The ids of the `n` closest points to `point` with their distances, closest first.
+    /// Points given to `new` are identified by their position in the slice.
+    pub fn nearest_n(&self, point: P, n: usize) -> Vec<(usize, i64)> {
+        let mut best = Vec::with_capacity(n + 1);
+
+        if n > 0 {
+            self.search(self.root, point, n, &mut best);
+        }
+
+        best
+    }
+
+    fn search(&self, node: Option<usize>, point: P, n: usize, best: &mut Vec<(usize, i64)>) {
+        let node = match node {
+            Some(idx) => &self.nodes[idx],
+            None => return,
+        };
+
+        let dist = self.metric.distance(node.point.0, point);
+
+        if !node.removed && (best.len() < n || dist < best[n - 1].1) {
+            let pos = best.iter().take_while(|(_, d)| *d <= dist).count();
+            best.insert(pos, (node.id, dist));
+            best.truncate(n);
+        }
+
+        let offset = i64::from(point.coord(node.axis)) - i64::from(node.point.0.coord(node.axis));
+        let (near, far) = if offset < 0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+
+        self.search(near, point, n, best);
+
+        if best.len() < n || self.metric.axis_bound(offset) < best[n - 1].1 {
+            self.search(far, point, n, best);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordError {
+    pub line: usize,
+    pub content: String,
+}
+
+impl fmt::Display for CoordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: expected comma separated coordinates, found `{}`",
+            self.line, self.content
+        )
+    }
+}
+
+impl Error for CoordError {}
+
+#[aoc_generator(day6)]
+pub fn parse_input(input: &str) -> Result<Vec<TaxicabSpatial>, CoordError> {
+    parse_coords(input)
+}
+
+/// Parses one `x, y` pair per line, skipping blank lines.
+pub fn parse_coords(input: &str) -> Result<Vec<TaxicabSpatial>, CoordError> {
+    parse_points(input)
+}
+
+/// Parses one point per line with a comma separated coordinate for each of the `DIM`
+/// axes, e.g. `1, -2, 3` in three dimensions. Blank lines are skipped.
+pub fn parse_points<P: Coords>(input: &str) -> Result<Vec<TaxicabSpatial<P>>, CoordError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(idx, l)| {
+            let coords: Vec<i32> = l
+                .split(',')
+                .map(|c| c.trim().parse())
+                .collect::<Result<_, _>>()
+                .ok()
+                .filter(|c: &Vec<i32>| c.len() == P::DIM)
+                .ok_or_else(|| CoordError {
+                    line: idx + 1,
+                    content: l.to_owned(),
+                })?;
+
+            Ok(TaxicabSpatial(P::from_coords(|axis| coords[axis])))
+        })
+        .collect()
+}
+
+// a cell `d` steps outside the bounding box is at least `axis_bound(d)` from every
+// point, so a region bounded by total distance can't reach further out than this.
+// `axis_bound` only grows with `d`, so the furthest margin that might still fit is
+// found by bisection, and never past i32.
+fn scan_margin<M: Metric>(metric: &M, points: usize, threshold: i64) -> i32 {
+    if points == 0 {
+        return 0;
+    }
+
+    let fits = |d: i32| (points as i64).saturating_mul(metric.axis_bound(i64::from(d))) < threshold;
+
+    let (mut lo, mut hi) = (0, i32::MAX);
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2 + 1;
+
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    lo
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionInfo<P = Point2<i32>> {
+    pub point: TaxicabSpatial<P>,
+    /// Cells in the region, its area in 2D and volume in 3D.
+    pub size: i32,
+    pub infinite: bool,
+    /// Lowest and highest corners of the cells the region covers, clipped to the scanned
+    /// box. `None` when every cell near the point is tied, e.g. duplicate inputs.
+    pub bounds: Option<(P, P)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionReport<P = Point2<i32>> {
+    /// Indexed like the input points.
+    pub regions: Vec<RegionInfo<P>>,
+    /// The input point with the largest finite region, the first one on ties.
+    pub largest: Option<usize>,
+}
+
+/// Which input point owns each cell of the metric's `RegionMetric::region_box`, which
+/// holds every finite region whole.
+#[derive(Debug, Clone)]
+pub struct RegionMap<P = Point2<i32>> {
+    pub points: Vec<TaxicabSpatial<P>>,
+    /// Lowest corner of the scanned box.
+    pub lo: P,
+    /// Highest corner of the scanned box.
+    pub hi: P,
+    /// In the order `box_cells` visits them, so row major in 2D. `None` where several
+    /// points are equally close.
+    pub owners: Vec<Option<usize>>,
+    /// Indexed like `points`.
+    pub infinite: Vec<bool>,
+}
+
+impl<P: Coords> RegionMap<P> {
+    pub fn new<M: RegionMetric<P>>(inp: &[TaxicabSpatial<P>], metric: M) -> RegionMap<P> {
+        let (lo, hi) = metric.region_box(inp);
+
+        let index = NearestIndex::new(inp, metric);
+
+        let owners: Vec<_> = box_cells(lo, hi)
+            .map(|cell| {
+                let closest_n = index.nearest_n(cell, 2);
+
+                if closest_n.len() == 2 && closest_n[0].1 == closest_n[1].1 {
+                    None
+                } else {
+                    Some(closest_n[0].0)
+                }
+            })
+            .collect();
+
+        let infinite = index.metric.infinite_regions(inp, (lo, hi), &owners);
+
+        RegionMap {
+            points: inp.to_vec(),
+            lo,
+            hi,
+            owners,
+            infinite,
+        }
+    }
+
+    fn cells<'a>(&'a self) -> impl Iterator<Item = (P, Option<usize>)> + 'a {
+        box_cells(self.lo, self.hi).zip(self.owners.iter().cloned())
+    }
+
+    /// Cells owned by each input point.
+    pub fn sizes(&self) -> Vec<i32> {
+        let mut sizes = vec![0; self.points.len()];
+
+        for owner in self.owners.iter().flatten() {
+            sizes[*owner] += 1;
+        }
+
+        sizes
+    }
+
+    pub fn report(&self) -> RegionReport<P> {
+        let mut regions: Vec<_> = self
+            .points
+            .iter()
+            .zip(&self.infinite)
+            .map(|(&point, &infinite)| RegionInfo {
+                point,
+                size: 0,
+                infinite,
+                bounds: None,
+            })
+            .collect();
+
+        for (cell, owner) in self.cells() {
+            let region = match owner {
+                Some(n) => &mut regions[n],
+                None => continue,
+            };
+
+            region.size += 1;
+            region.bounds = Some(match region.bounds {
+                Some((lo, hi)) => (
+                    P::from_coords(|axis| lo.coord(axis).min(cell.coord(axis))),
+                    P::from_coords(|axis| hi.coord(axis).max(cell.coord(axis))),
+                ),
+                None => (cell, cell),
+            });
+        }
+
+        let largest = regions
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| !r.infinite)
+            .min_by_key(|(idx, r)| (Reverse(r.size), *idx))
+            .map(|(idx, _)| idx);
+
+        RegionReport { regions, largest }
+    }
+}
+
+impl RegionMap {
+    pub fn width(&self) -> usize {
+        (self.hi.x - self.lo.x + 1) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.hi.y - self.lo.y + 1) as usize
+    }
+
+    /// The point owning `point`, or `Some(None)` where two or more points tie for it.
+    /// Returns `None` for cells outside the map.
+    pub fn owner(&self, point: Point2<i32>) -> Option<Option<usize>> {
+        if point.x < self.lo.x || point.x > self.hi.x || point.y < self.lo.y || point.y > self.hi.y
+        {
+            return None;
+        }
+
+        let x = (i64::from(point.x) - i64::from(self.lo.x)) as usize;
+        let y = (i64::from(point.y) - i64::from(self.lo.y)) as usize;
+
+        Some(self.owners[y * self.width() + x])
+    }
+
+    /// The grid in the puzzle's notation: the input points in upper case, the rest of
+    /// their region in lower case and `.` for ties, followed by a line listing the
+    /// regions that extend forever. Returns `None` past 26 points.
+    pub fn render_text(&self) -> Option<String> {
+        if self.points.len() > 26 {
+            return None;
+        }
+
+        let letter = |n: usize| (b'a' + n as u8) as char;
+
+        let mut out = String::with_capacity((self.width() + 1) * (self.height() + 1));
+
+        for (cell, owner) in self.cells() {
+            out.push(match owner {
+                None => '.',
+                Some(n) if self.points[n].0 == cell => letter(n).to_ascii_uppercase(),
+                Some(n) => letter(n),
+            });
+
+            if cell.x == self.hi.x {
+                out.push('\n');
+            }
+        }
+
+        out.push_str("infinite:");
+
+        for (n, _) in self.infinite.iter().enumerate().filter(|(_, &inf)| inf) {
+            out.push(' ');
+            out.push(letter(n).to_ascii_uppercase());
+        }
+
+        out.push('\n');
+
+        Some(out)
+    }
+
+    /// Writes the grid as a binary PPM with a colour per region. Regions that extend
+    /// forever are drawn darker, ties in black and the input points in white.
+    pub fn write_ppm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width(), self.height())?;
+
+        let mut pixels = Vec::with_capacity(self.owners.len() * 3);
+
+        for owner in &self.owners {
+            let colour = match *owner {
+                None => [0, 0, 0],
+                Some(n) if self.infinite[n] => region_colour(n, 0.45),
+                Some(n) => region_colour(n, 0.9),
+            };
+
+            pixels.extend_from_slice(&colour);
+        }
+
+        for point in &self.points {
+            let idx =
+                (point.0.y - self.lo.y) as usize * self.width() + (point.0.x - self.lo.x) as usize;
+            pixels[idx * 3..idx * 3 + 3].copy_from_slice(&[255, 255, 255]);
+        }
+
+        out.write_all(&pixels)
+    }
+}
+
+// spreads hues around the wheel by the golden angle so neighbouring ids differ
+fn region_colour(n: usize, value: f64) -> [u8; 3] {
+    let hue = (n as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+
+    let channel = |c: f64| ((0.25 + 0.75 * c) * value * 255.0) as u8;
+
+    [channel(r), channel(g), channel(b)]
+}
+
+/// Every input point's region under `metric`, along with which one is the largest
+/// finite region.
+pub fn region_report<P: Coords, M: RegionMetric<P>>(
+    inp: &[TaxicabSpatial<P>],
+    metric: M,
+) -> RegionReport<P> {
+    RegionMap::new(inp, metric).report()
+}
+
+/// The size of the largest region of cells closest to a single input point, ignoring
+/// regions that extend forever. `None` when every region does.
+pub fn largest_finite_region<P: Coords, M: RegionMetric<P>>(
+    inp: &[TaxicabSpatial<P>],
+    metric: M,
+) -> Option<i32> {
+    let report = region_report(inp, metric);
+
+    report.largest.map(|idx| report.regions[idx].size)
+}
+
+/// The number of cells whose total distance to every input point is under `threshold`,
+/// none when there are no points.
+pub fn total_distance_region<P: Coords, M: Metric>(
+    inp: &[TaxicabSpatial<P>],
+    metric: M,
+    threshold: i64,
+) -> i64 {
+    if inp.is_empty() {
+        return 0;
+    }
+
+    let margin = scan_margin(&metric, inp.len(), threshold);
+    let (lo, hi) = grow_box(bounding_box(inp), margin);
+
+    box_cells(lo, hi)
+        .filter(|&cell| {
+            let total: i64 = inp.iter().map(|e| metric.distance(e.0, cell)).sum();
+
+            total < threshold
+        })
+        .count() as i64
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SiteCell {
+    dist: i64,
+    // `None` where several sites are equally close
+    owner: Option<usize>,
+}
+
+/// How one site's region size moved during an update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionChange {
+    pub site: usize,
+    pub before: i32,
+    pub after: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteUpdate {
+    /// Every site whose region changed size, ordered by site id.
+    pub changes: Vec<RegionChange>,
+    /// The site with the largest finite region after the update, the lowest id on ties.
+    pub largest: Option<usize>,
+}
+
+/// A set of points that can be inserted and removed while keeping every taxicab region
+/// up to date, in the same grown bounding box `RegionMap` scans.
+///
+/// Under L1 a cell no further from a site than from its closest one can step towards
+/// that site and stay so, which makes the cells an update can touch a connected patch
+/// around the changed site. Each update flood fills that patch and adds or drops the
+/// strips where the box grew or shrank, rather than rescanning the whole box.
+///
+/// The nearest site index, the bounding box, how many cells on the box's faces each site
+/// owns and the ranking of finite regions are all kept up to date the same way, so an
+/// update never has to visit every site.
+#[derive(Debug, Default)]
+pub struct SiteSet {
+    // indexed by site id, `None` once removed
+    sites: Vec<Option<TaxicabSpatial>>,
+    sizes: Vec<i32>,
+    // cells on the faces of the window owned by each site, which make it infinite
+    border: Vec<i32>,
+    // live sites with finite regions, largest region first
+    finite: BTreeSet<(Reverse<i32>, usize)>,
+    index: NearestIndex<Taxicab>,
+    // how many live sites sit at each x and y, for the bounding box
+    xs: BTreeMap<i32, i32>,
+    ys: BTreeMap<i32, i32>,
+    window: Option<(Point2<i32>, Point2<i32>)>,
+    cells: HashMap<Point2<i32>, SiteCell>,
+}
+
+impl SiteSet {
+    pub fn new() -> SiteSet {
+        SiteSet::default()
+    }
+
+    /// The ids and positions of the sites currently in the set.
+    pub fn sites<'a>(&'a self) -> impl Iterator<Item = (usize, TaxicabSpatial)> + 'a {
+        self.sites
+            .iter()
+            .enumerate()
+            .filter_map(|(id, site)| site.map(|s| (id, s)))
+    }
+
+    pub fn size(&self, site: usize) -> i32 {
+        self.sizes[site]
+    }
+
+    pub fn is_infinite(&self, site: usize) -> bool {
+        self.border[site] > 0
+    }
+
+    pub fn largest(&self) -> Option<usize> {
+        self.finite.iter().next().map(|&(_, id)| id)
+    }
+
+    /// Adds a point, returning the id it was given along with the regions it changed.
+    pub fn insert(&mut self, point: TaxicabSpatial) -> (usize, SiteUpdate) {
+        let site = self.sites.len();
+
+        self.sites.push(Some(point));
+        self.sizes.push(0);
+        self.border.push(0);
+        self.finite.insert((Reverse(0), site));
+        self.index.insert(site, point);
+        self.tally(point, 1);
+
+        let mut before = HashMap::new();
+        before.insert(site, 0);
+
+        let window = self.bounding_window().unwrap();
+
+        let fresh = match self.set_window(Some(window)) {
+            Some(old) => ring(window, old).to_vec(),
+            None => vec![window],
+        };
 
-impl TaxicabSpatial {
-    fn new(x: i32, y: i32) -> TaxicabSpatial {
-        TaxicabSpatial(Point2::new(x, y))
+        for (lo, hi) in fresh {
+            for cell in box_cells(lo, hi) {
+                let closest = nearest_cell(&self.index, cell);
+                self.set_cell(cell, Some(closest), &mut before);
+            }
+        }
+
+        for (cell, dist) in self.reach(point.0) {
+            let current = self.cells[&cell];
+
+            let updated = if dist < current.dist {
+                SiteCell {
+                    dist,
+                    owner: Some(site),
+                }
+            } else if current.owner == Some(site) {
+                current
+            } else {
+                SiteCell { dist, owner: None }
+            };
+
+            self.set_cell(cell, Some(updated), &mut before);
+        }
+
+        (site, self.finish_update(before))
     }
-}
 
-fn taxicab(a: Point2<i32>, b: Point2<i32>) -> i32 {
-    (a.x - b.x).abs() + (a.y - b.y).abs()
-}
+    /// Removes a site, returning `None` if there is no site with that id.
+    pub fn remove(&mut self, site: usize) -> Option<SiteUpdate> {
+        let point = self.sites.get_mut(site)?.take()?;
+
+        self.finite.remove(&(Reverse(self.sizes[site]), site));
+        self.index.remove(site);
+        self.tally(point, -1);
+
+        let mut before = HashMap::new();
+
+        let window = match self.bounding_window() {
+            Some(window) => window,
+            None => {
+                before.insert(site, self.sizes[site]);
+                self.sizes[site] = 0;
+                self.border[site] = 0;
+                self.cells.clear();
+                self.window = None;
+
+                return Some(self.finish_update(before));
+            }
+        };
+
+        for (cell, _) in self.reach(point.0) {
+            let closest = nearest_cell(&self.index, cell);
+            self.set_cell(cell, Some(closest), &mut before);
+        }
+
+        let old = self.set_window(Some(window)).unwrap();
+
+        for (lo, hi) in ring(old, window).iter() {
+            for cell in box_cells(*lo, *hi) {
+                self.set_cell(cell, None, &mut before);
+            }
+        }
+
+        Some(self.finish_update(before))
+    }
+
+    fn tally(&mut self, point: TaxicabSpatial, delta: i32) {
+        for (counts, coord) in [(&mut self.xs, point.0.x), (&mut self.ys, point.0.y)] {
+            let count = counts.entry(coord).or_insert(0);
+            *count += delta;
+
+            if *count == 0 {
+                counts.remove(&coord);
+            }
+        }
+    }
+
+    // the bounding box of the live sites grown by one, `None` when there are none
+    fn bounding_window(&self) -> Option<(Point2<i32>, Point2<i32>)> {
+        let (&min_x, _) = self.xs.iter().next()?;
+        let (&max_x, _) = self.xs.iter().next_back()?;
+        let (&min_y, _) = self.ys.iter().next()?;
+        let (&max_y, _) = self.ys.iter().next_back()?;
+
+        Some(grow_box(
+            (Point2::new(min_x, min_y), Point2::new(max_x, max_y)),
+            1,
+        ))
+    }
+
+    // moves the window, recounting the cells each site owns on its faces, and returns the
+    // old one
+    fn set_window(
+        &mut self,
+        window: Option<(Point2<i32>, Point2<i32>)>,
+    ) -> Option<(Point2<i32>, Point2<i32>)> {
+        let old = self.window;
+
+        if old == window {
+            return old;
+        }
+
+        self.recount_face(-1);
+        self.window = window;
+        self.recount_face(1);
+
+        old
+    }
+
+    fn recount_face(&mut self, delta: i32) {
+        for cell in self.window.into_iter().flat_map(face_cells) {
+            if let Some(owner) = self.cells.get(&cell).and_then(|c| c.owner) {
+                self.adjust(owner, 0, delta);
+            }
+        }
+    }
+
+    // the cells no further from `point` than from their closest site, along with their
+    // distance to it
+    fn reach(&self, point: Point2<i32>) -> Vec<(Point2<i32>, i64)> {
+        let (lo, hi) = self.window.unwrap();
+
+        let mut seen = HashSet::new();
+        let mut queue = vec![point];
+        let mut found = Vec::new();
+
+        while let Some(cell) = queue.pop() {
+            let inside = lo.x <= cell.x && cell.x <= hi.x && lo.y <= cell.y && cell.y <= hi.y;
+
+            if !inside || !seen.insert(cell) {
+                continue;
+            }
+
+            let dist = Taxicab.distance(point, cell);
+
+            if dist > self.cells[&cell].dist {
+                continue;
+            }
+
+            found.push((cell, dist));
+
+            queue.extend(&[
+                Point2::new(cell.x - 1, cell.y),
+                Point2::new(cell.x + 1, cell.y),
+                Point2::new(cell.x, cell.y - 1),
+                Point2::new(cell.x, cell.y + 1),
+            ]);
+        }
+
+        found
+    }
+
+    // stores a cell, or forgets it given `None`, keeping region sizes in step and
+    // remembering the size each touched site started the update with
+    fn set_cell(
+        &mut self,
+        cell: Point2<i32>,
+        new: Option<SiteCell>,
+        before: &mut HashMap<usize, i32>,
+    ) {
+        let old = match new {
+            Some(new) => self.cells.insert(cell, new),
+            None => self.cells.remove(&cell),
+        };
+
+        let old_owner = old.and_then(|c| c.owner);
+        let new_owner = new.and_then(|c| c.owner);
+
+        if old_owner == new_owner {
+            return;
+        }
+
+        let on_face = self.window.is_some_and(|window| is_on_face(window, cell));
+
+        for (site, delta) in old_owner
+            .map(|s| (s, -1))
+            .into_iter()
+            .chain(new_owner.map(|s| (s, 1)))
+        {
+            before.entry(site).or_insert(self.sizes[site]);
+            self.adjust(site, delta, if on_face { delta } else { 0 });
+        }
+    }
+
+    // changes a site's size and face cell count, keeping the finite regions ranked
+    fn adjust(&mut self, site: usize, size: i32, border: i32) {
+        if self.border[site] == 0 {
+            self.finite.remove(&(Reverse(self.sizes[site]), site));
+        }
+
+        self.sizes[site] += size;
+        self.border[site] += border;
 
-impl SpatialObject for TaxicabSpatial {
-    type Point = Point2<i32>;
+        if self.border[site] == 0 && self.sites[site].is_some() {
+            self.finite.insert((Reverse(self.sizes[site]), site));
+        }
+    }
 
-    fn mbr(&self) -> BoundingRect<Self::Point> {
-        self.0.mbr()
+    fn finish_update(&mut self, before: HashMap<usize, i32>) -> SiteUpdate {
+        let changes = before
+            .into_iter()
+            .map(|(site, before)| RegionChange {
+                site,
+                before,
+                after: self.sizes[site],
+            })
+            .filter(|c| c.before != c.after)
+            .sorted_by_key(|c| c.site)
+            .collect();
+
+        SiteUpdate {
+            changes,
+            largest: self.largest(),
+        }
     }
+}
+
+fn nearest_cell(index: &NearestIndex<Taxicab>, cell: Point2<i32>) -> SiteCell {
+    let closest_n = index.nearest_n(cell, 2);
 
-    fn distance2(&self, point: &Self::Point) -> i32 {
-        taxicab(self.0, *point).pow(2)
+    SiteCell {
+        dist: closest_n[0].1,
+        owner: if closest_n.len() == 2 && closest_n[0].1 == closest_n[1].1 {
+            None
+        } else {
+            Some(closest_n[0].0)
+        },
     }
 }
 
-lazy_static! {
-    static ref COORD_RE: Regex = Regex::new(r"(?P<x>\d+),\s*(?P<y>\d+)").unwrap();
+fn is_on_face((lo, hi): (Point2<i32>, Point2<i32>), cell: Point2<i32>) -> bool {
+    let inside = lo.x <= cell.x && cell.x <= hi.x && lo.y <= cell.y && cell.y <= hi.y;
+
+    inside && (cell.x == lo.x || cell.x == hi.x || cell.y == lo.y || cell.y == hi.y)
 }
 
-#[aoc_generator(day6)]
-pub fn parse_input(input: &str) -> Vec<TaxicabSpatial> {
-    COORD_RE
-        .captures_iter(input)
-        .map(|c| {
-            TaxicabSpatial::new(
-                c["x"].parse::<i32>().unwrap(),
-                c["y"].parse::<i32>().unwrap(),
-            )
-        })
-        .collect()
+// the cells on the faces of a window
+fn face_cells(window: (Point2<i32>, Point2<i32>)) -> impl Iterator<Item = Point2<i32>> {
+    ring(window, grow_box(window, -1))
+        .to_vec()
+        .into_iter()
+        .flat_map(|(lo, hi)| box_cells(lo, hi))
 }
 
-#[derive(Default, Debug)]
-struct Region {
-    members: i32,
-    infinite: bool,
+// the parts of `outer` outside `inner`, which it contains, as boxes that may be empty
+fn ring(
+    (outer_lo, outer_hi): (Point2<i32>, Point2<i32>),
+    (inner_lo, inner_hi): (Point2<i32>, Point2<i32>),
+) -> [(Point2<i32>, Point2<i32>); 4] {
+    [
+        (outer_lo, Point2::new(outer_hi.x, inner_lo.y - 1)),
+        (Point2::new(outer_lo.x, inner_hi.y + 1), outer_hi),
+        (
+            Point2::new(outer_lo.x, inner_lo.y),
+            Point2::new(inner_lo.x - 1, inner_hi.y),
+        ),
+        (
+            Point2::new(inner_hi.x + 1, inner_lo.y),
+            Point2::new(outer_hi.x, inner_hi.y),
+        ),
+    ]
 }
 
 #[aoc(day6, part1)]
 pub fn part1(inp: &[TaxicabSpatial]) -> i32 {
-    let tree = RTree::bulk_load(inp.to_vec());
+    largest_finite_region(inp, Taxicab).unwrap()
+}
 
-    let mut regions: HashMap<TaxicabSpatial, Region> = HashMap::new();
+#[aoc(day6, part1, Incremental)]
+pub fn part1_incremental(inp: &[TaxicabSpatial]) -> i32 {
+    let mut sites = SiteSet::new();
 
-    let bounds = tree.mbr().unwrap();
-    let lower = bounds.lower();
-    let upper = bounds.upper();
+    for &point in inp {
+        sites.insert(point);
+    }
 
-    for y in lower.y - 1..upper.y + 2 {
-        for x in lower.x - 1..upper.x + 2 {
-            let point = TaxicabSpatial::new(x, y);
-            let closest_n = tree.nearest_n_neighbors(&point.0, 2);
+    sites.size(sites.largest().unwrap())
+}
 
-            if closest_n.len() == 2
-                && taxicab(point.0, closest_n[0].0) == taxicab(point.0, closest_n[1].0)
-            {
-                continue;
-            }
+#[aoc(day6, part2)]
+pub fn part2(inp: &[TaxicabSpatial]) -> i64 {
+    total_distance_region(inp, Taxicab, 10000)
+}
 
-            let closest = closest_n[0];
+// sum of |c - p| over all `coords` for every c in lo..=hi
+fn axis_distance_sums(mut coords: Vec<i64>, lo: i64, hi: i64) -> Vec<i64> {
+    coords.sort_unstable();
 
-            let region = regions.entry(*closest).or_default();
+    let n = coords.len() as i64;
+    let mut sum: i64 = coords.iter().map(|&p| (lo - p).abs()).sum();
+    let mut below = 0;
+    let mut sums = Vec::with_capacity((hi - lo + 1) as usize);
 
-            region.members += 1;
+    for c in lo..=hi {
+        sums.push(sum);
 
-            if x < lower.x || x > upper.x || y < lower.y || y > upper.y {
-                region.infinite = true;
-            }
+        while below < coords.len() && coords[below] <= c {
+            below += 1;
         }
+
+        // stepping to c + 1 moves away from everything at or below c and towards
+        // everything above it
+        sum += 2 * below as i64 - n;
     }
 
-    regions
-        .into_iter()
-        .filter_map(|(_k, v)| if v.infinite { None } else { Some(v.members) })
-        .max()
-        .unwrap()
+    sums
 }
 
-#[aoc(day6, part2)]
-pub fn part2(inp: &[TaxicabSpatial]) -> i32 {
-    let (min_x, max_x) = inp.iter().map(|c| c.0.x).minmax().into_option().unwrap();
-    let (min_y, max_y) = inp.iter().map(|c| c.0.y).minmax().into_option().unwrap();
+// the sums are convex in c, falling then rising, so merging the two halves sorts them
+fn sort_convex(sums: &[i64]) -> Vec<i64> {
+    let split = sums
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, s)| s)
+        .map_or(0, |(idx, _)| idx);
+
+    let mut falling = sums[..split].iter().rev().peekable();
+    let mut rising = sums[split..].iter().peekable();
+    let mut sorted = Vec::with_capacity(sums.len());
+
+    loop {
+        let next = match (falling.peek(), rising.peek()) {
+            (Some(a), Some(b)) if a < b => falling.next(),
+            (_, Some(_)) => rising.next(),
+            (Some(_), None) => falling.next(),
+            (None, None) => break,
+        };
+
+        sorted.extend(next);
+    }
+
+    sorted
+}
+
+// taxicab distance splits into an x and a y term, so the total distance of (x, y) is
+// just x_sums[x] + y_sums[y] and we only need to count pairs under the threshold
+fn count_separable(inp: &[TaxicabSpatial], threshold: i64) -> i64 {
+    if inp.is_empty() {
+        return 0;
+    }
+
+    let xs: Vec<i64> = inp.iter().map(|c| i64::from(c.0.x)).collect();
+    let ys: Vec<i64> = inp.iter().map(|c| i64::from(c.0.y)).collect();
 
+    let (min_x, max_x) = xs.iter().cloned().minmax().into_option().unwrap();
+    let (min_y, max_y) = ys.iter().cloned().minmax().into_option().unwrap();
+
+    let margin = i64::from(scan_margin(&Taxicab, inp.len(), threshold));
+
+    let x_sums = sort_convex(&axis_distance_sums(xs, min_x - margin, max_x + margin));
+    let y_sums = sort_convex(&axis_distance_sums(ys, min_y - margin, max_y + margin));
+
+    // as the x term grows, the number of y terms that still fit can only shrink
+    let mut fits = y_sums.len();
     let mut region_size = 0;
 
-    for y in min_y - 1..max_y + 2 {
-        for x in min_x - 1..max_x + 2 {
-            if inp.iter().map(|e| taxicab(e.0, Point2::new(x, y))).sum::<i32>() < 10000 {
-                region_size += 1;
-            }
+    for sx in x_sums {
+        while fits > 0 && sx + y_sums[fits - 1] >= threshold {
+            fits -= 1;
         }
+
+        region_size += fits as i64;
     }
 
     region_size
 }
 
+#[aoc(day6, part2, Separable)]
+pub fn part2_separable(inp: &[TaxicabSpatial]) -> i64 {
+    count_separable(inp, 10000)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{parse_input, part1};
+    use super::{
+        bounding_box, box_cells, count_separable, grow_box, largest_finite_region, parse_coords,
+        parse_input, parse_points, part1, part1_incremental, region_report, scan_margin,
+        total_distance_region, Chebyshev, CoordError, Metric, NearestIndex, RegionMap,
+        RegionMetric, SiteSet, SquaredEuclidean, Taxicab, TaxicabSpatial,
+    };
+    use cgmath::{Point2, Point3};
+    use itertools::Itertools;
+    use rand::prng::XorShiftRng;
+    use rand::{Rng, SeedableRng};
 
-    #[test]
-    fn t1() {
-        let inp = r#"1, 1
+    const INPUT: &str = r#"1, 1
 1, 6
 8, 3
 3, 4
 5, 5
 8, 9"#;
 
-        let parsed = parse_input(inp);
+    #[test]
+    fn t1() {
+        let parsed = parse_input(INPUT).unwrap();
 
         let result = part1(&parsed);
 
         assert_eq!(result, 17);
     }
+
+    #[test]
+    fn t2() {
+        let parsed = parse_input(INPUT).unwrap();
+
+        let result = total_distance_region(&parsed, Taxicab, 32);
+
+        assert_eq!(result, 16);
+        assert_eq!(count_separable(&parsed, 32), 16);
+
+        for threshold in 0..200 {
+            assert_eq!(
+                count_separable(&parsed, threshold),
+                total_distance_region(&parsed, Taxicab, threshold)
+            );
+        }
+    }
+
+    #[test]
+    fn region_past_bounding_box() {
+        let parsed = parse_input("10, 10").unwrap();
+
+        // the diamond |x| + |y| <= 9 around the lone point
+        assert_eq!(total_distance_region(&parsed, Taxicab, 10), 181);
+        assert_eq!(count_separable(&parsed, 10), 181);
+    }
+
+    #[test]
+    fn no_points() {
+        assert_eq!(scan_margin(&Taxicab, 0, 10), 0);
+        assert_eq!(total_distance_region::<Point2<i32>, _>(&[], Taxicab, 10), 0);
+        assert_eq!(count_separable(&[], 10), 0);
+
+        // the margin stops at the edge of i32 however loose the threshold
+        assert_eq!(scan_margin(&Taxicab, 1, i64::MAX), i32::MAX);
+        assert_eq!(scan_margin(&SquaredEuclidean, 3, i64::MAX), 1_753_413_056);
+    }
+
+    #[test]
+    fn separable_wide_spread() {
+        let parsed = parse_input("0, 0\n3000000, 0").unwrap();
+
+        // only the row between the two points stays under the threshold
+        assert_eq!(count_separable(&parsed, 3_000_002), 3_000_001);
+    }
+
+    fn index_matches_brute_force<M: Metric + Copy>(metric: M) {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+
+        for _ in 0..200 {
+            let spread = rng.gen_range(1, 50);
+            let points: Vec<_> = (0..rng.gen_range(1, 40))
+                .map(|_| {
+                    TaxicabSpatial::new(
+                        rng.gen_range(-spread, spread),
+                        rng.gen_range(-spread, spread),
+                    )
+                })
+                .collect();
+
+            let index = NearestIndex::new(&points, metric);
+
+            for _ in 0..20 {
+                let query = Point2::new(
+                    rng.gen_range(-2 * spread, 2 * spread),
+                    rng.gen_range(-2 * spread, 2 * spread),
+                );
+                let n = rng.gen_range(1, 5);
+
+                let mut expected: Vec<_> =
+                    points.iter().map(|p| metric.distance(p.0, query)).collect();
+                expected.sort();
+                expected.truncate(n);
+
+                let found = index.nearest_n(query, n);
+
+                for &(id, d) in &found {
+                    assert_eq!(metric.distance(points[id].0, query), d);
+                }
+
+                let found: Vec<_> = found.iter().map(|(_, d)| *d).collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn index_updates() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut index = NearestIndex::new(&[], Taxicab);
+        let mut points = Vec::new();
+
+        for id in 0..500 {
+            if !points.is_empty() && rng.gen_range(0, 3) == 0 {
+                let (id, _) = points.swap_remove(rng.gen_range(0, points.len()));
+                assert!(index.remove(id));
+                assert!(!index.remove(id));
+            } else {
+                let point = TaxicabSpatial::new(rng.gen_range(-30, 30), rng.gen_range(-30, 30));
+                index.insert(id, point);
+                points.push((id, point));
+            }
+
+            let query = Point2::new(rng.gen_range(-40, 40), rng.gen_range(-40, 40));
+
+            let mut expected: Vec<_> = points
+                .iter()
+                .map(|(_, p)| Taxicab.distance(p.0, query))
+                .collect();
+            expected.sort();
+            expected.truncate(3);
+
+            let found = index.nearest_n(query, 3);
+
+            for &(id, d) in &found {
+                let (_, point) = points.iter().find(|&&(p, _)| p == id).unwrap();
+                assert_eq!(Taxicab.distance(point.0, query), d);
+            }
+
+            let found: Vec<_> = found.iter().map(|(_, d)| *d).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn index_taxicab() {
+        index_matches_brute_force(Taxicab);
+    }
+
+    #[test]
+    fn index_chebyshev() {
+        index_matches_brute_force(Chebyshev);
+    }
+
+    #[test]
+    fn index_squared_euclidean() {
+        index_matches_brute_force(SquaredEuclidean);
+    }
+
+    #[test]
+    fn other_metrics() {
+        let parsed = parse_input(INPUT).unwrap();
+
+        assert_eq!(largest_finite_region(&parsed, Taxicab), Some(17));
+        assert_eq!(largest_finite_region(&parsed, Chebyshev), Some(10));
+        assert_eq!(largest_finite_region(&parsed, SquaredEuclidean), Some(16));
+
+        let parsed = parse_input("0, 0").unwrap();
+
+        assert_eq!(total_distance_region(&parsed, Chebyshev, 3), 25);
+        assert_eq!(total_distance_region(&parsed, SquaredEuclidean, 5), 13);
+    }
+
+    #[test]
+    fn finite_past_the_box() {
+        let parsed = parse_input("0, 0\n10, 0\n5, 1\n5, 10").unwrap();
+
+        // every region reaches past the box under L1
+        assert_eq!(largest_finite_region(&parsed, Taxicab), None);
+
+        // but under L∞ the one around 5, 1 is closed off below the bottom edge
+        let report = region_report(&parsed, Chebyshev);
+        assert_eq!(report.largest, Some(2));
+        assert_eq!(report.regions[2].size, 45);
+        assert!(report.regions[2].bounds.unwrap().0.y < 0);
+        assert_eq!(report.regions.iter().filter(|r| r.infinite).count(), 3);
+
+        // and under L2 it reaches eleven rows below the points
+        let report = region_report(&parsed, SquaredEuclidean);
+        assert_eq!(report.largest, Some(2));
+        assert_eq!(report.regions[2].size, 59);
+        assert_eq!(report.regions[2].bounds.unwrap().0.y, -11);
+    }
+
+    #[test]
+    fn euclidean_regions() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+
+        for _ in 0..50 {
+            let points: Vec<_> = (0..rng.gen_range(2, 8))
+                .map(|_| TaxicabSpatial::new(rng.gen_range(-5, 5), rng.gen_range(-5, 5)))
+                .collect();
+
+            // every cell far enough out to be past any finite region of points this close
+            let (lo, hi) = grow_box(bounding_box(&points), 80);
+            let mut sizes = vec![0; points.len()];
+            let mut infinite = vec![false; points.len()];
+
+            for cell in box_cells(lo, hi) {
+                let dists: Vec<_> = points
+                    .iter()
+                    .map(|p| SquaredEuclidean.distance(p.0, cell))
+                    .collect();
+                let closest = *dists.iter().min().unwrap();
+
+                if dists.iter().filter(|&&d| d == closest).count() > 1 {
+                    continue;
+                }
+
+                let owner = dists.iter().position(|&d| d == closest).unwrap();
+                sizes[owner] += 1;
+
+                if cell.x == lo.x || cell.x == hi.x || cell.y == lo.y || cell.y == hi.y {
+                    infinite[owner] = true;
+                }
+            }
+
+            let report = region_report(&points, SquaredEuclidean);
+
+            for (idx, region) in report.regions.iter().enumerate() {
+                assert_eq!(region.infinite, infinite[idx], "{:?}", points);
+
+                if !region.infinite {
+                    assert_eq!(region.size, sizes[idx], "{:?}", points);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn far_apart() {
+        let parsed = parse_input("-2000000000, 0\n2000000000, 0").unwrap();
+
+        assert_eq!(Taxicab.distance(parsed[0].0, parsed[1].0), 4_000_000_000);
+        assert_eq!(Chebyshev.distance(parsed[0].0, parsed[1].0), 4_000_000_000);
+
+        let index = NearestIndex::new(&parsed, Taxicab);
+        assert_eq!(
+            index.nearest_n(Point2::new(1_999_999_999, 5), 2),
+            vec![(1, 6), (0, 4_000_000_004)]
+        );
+
+        let grown = (
+            Point2::new(i32::MIN, -i32::MAX),
+            Point2::new(i32::MAX, i32::MAX),
+        );
+        assert_eq!(grow_box(bounding_box(&parsed), i32::MAX), grown);
+
+        // the L∞ margin is the whole width, which doesn't fit in i32
+        assert_eq!(Chebyshev.region_box(&parsed), grown);
+    }
+
+    #[test]
+    fn negative_quadrants() {
+        // the puzzle example shifted so it straddles both axes
+        let inp = r#"-4, -4
+-4, 1
+3, -2
+-2, -1
+0, 0
+3, 4"#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        assert_eq!(parsed[0], TaxicabSpatial::new(-4, -4));
+        assert_eq!(part1(&parsed), 17);
+        assert_eq!(total_distance_region(&parsed, Taxicab, 32), 16);
+        assert_eq!(count_separable(&parsed, 32), 16);
+    }
+
+    #[test]
+    fn bad_coords() {
+        assert_eq!(
+            parse_coords("1, 2\n\n3; 4\n"),
+            Err(CoordError {
+                line: 3,
+                content: "3; 4".to_owned(),
+            })
+        );
+
+        assert_eq!(parse_coords("1, 2\n99999999999, 0").unwrap_err().line, 2);
+        assert_eq!(parse_input("1, 2\nx").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn render_text() {
+        let parsed = parse_input(INPUT).unwrap();
+        let map = RegionMap::new(&parsed, Taxicab);
+
+        assert_eq!(
+            map.render_text().unwrap(),
+            "aaaaa.cccc
+aAaaa.cccc
+aaaddecccc
+aadddeccCc
+..dDdeeccc
+bb.deEeecc
+bBb.eeee..
+bbb.eeefff
+bbb.eeffff
+bbb.ffffFf
+bbb.ffffff
+infinite: A B C F
+"
+        );
+    }
+
+    #[test]
+    fn owner_lookup() {
+        let parsed = parse_input(INPUT).unwrap();
+        let map = RegionMap::new(&parsed, Taxicab);
+
+        assert_eq!(map.owner(Point2::new(1, 1)), Some(Some(0)));
+        assert_eq!(map.owner(Point2::new(5, 0)), Some(None));
+        assert_eq!(map.owner(Point2::new(9, 0)), Some(Some(2)));
+        assert_eq!(map.owner(Point2::new(10, 0)), None);
+        assert_eq!(map.owner(Point2::new(0, 11)), None);
+        assert_eq!(map.owner(Point2::new(-1, 5)), None);
+    }
+
+    #[test]
+    fn render_ppm() {
+        let parsed = parse_input(INPUT).unwrap();
+        let map = RegionMap::new(&parsed, Taxicab);
+
+        let mut out = Vec::new();
+        map.write_ppm(&mut out).unwrap();
+
+        let header = b"P6\n10 11\n255\n";
+        assert_eq!(&out[..header.len()], &header[..]);
+        assert_eq!(out.len(), header.len() + 10 * 11 * 3);
+
+        // the tie at (5, 0) and the point at (1, 1)
+        let pixel = |x: usize, y: usize| {
+            let idx = header.len() + (y * 10 + x) * 3;
+            out[idx..idx + 3].to_vec()
+        };
+        assert_eq!(pixel(5, 0), vec![0, 0, 0]);
+        assert_eq!(pixel(1, 1), vec![255, 255, 255]);
+    }
+
+    #[test]
+    fn report() {
+        let parsed = parse_input(&format!("{}\n3, 4", INPUT)).unwrap();
+        let report = region_report(&parsed, Taxicab);
+
+        assert_eq!(report.largest, Some(4));
+
+        let largest = &report.regions[4];
+        assert_eq!(largest.point, TaxicabSpatial::new(5, 5));
+        assert_eq!(largest.size, 17);
+        assert!(!largest.infinite);
+        assert_eq!(largest.bounds, Some((Point2::new(4, 2), Point2::new(7, 8))));
+
+        let corner = &report.regions[0];
+        assert!(corner.infinite);
+        assert_eq!(corner.bounds.unwrap().0, Point2::new(0, 0));
+
+        // the duplicated point ties with itself everywhere
+        assert_eq!(report.regions[3].size, 0);
+        assert_eq!(report.regions[3].bounds, None);
+    }
+
+    #[test]
+    fn three_dimensions() {
+        // the corners of a cube, the middle of each face and the centre
+        let inp = "0, 0, 0\n0, 0, 6\n0, 6, 0\n0, 6, 6\n6, 0, 0\n6, 0, 6\n6, 6, 0\n6, 6, 6
+3, 3, 0\n3, 3, 6\n3, 0, 3\n3, 6, 3\n0, 3, 3\n6, 3, 3\n3, 3, 3";
+
+        let parsed: Vec<TaxicabSpatial<Point3<i32>>> = parse_points(inp).unwrap();
+        let report = region_report(&parsed, Taxicab);
+
+        assert_eq!(report.largest, Some(14));
+        assert_eq!(report.regions[14].size, 27);
+        assert_eq!(
+            report.regions[14].bounds,
+            Some((Point3::new(2, 2, 2), Point3::new(4, 4, 4)))
+        );
+        assert!(report.regions[..14].iter().all(|r| r.infinite));
+        assert_eq!(largest_finite_region(&parsed, Taxicab), Some(27));
+
+        assert_eq!(total_distance_region(&parsed, Taxicab, 140), 499);
+
+        // the octahedron |x| + |y| + |z| <= 2
+        let lone = [TaxicabSpatial(Point3::new(5, -5, 5))];
+        assert_eq!(total_distance_region(&lone, Taxicab, 3), 25);
+
+        assert_eq!(
+            parse_points::<Point3<i32>>("1, 2, 3\n1, 2")
+                .unwrap_err()
+                .line,
+            2
+        );
+    }
+
+    #[test]
+    fn four_dimensions() {
+        let lone: Vec<TaxicabSpatial<[i32; 4]>> = parse_points("1, 2, 3, 4").unwrap();
+
+        assert_eq!(lone[0], TaxicabSpatial([1, 2, 3, 4]));
+        assert_eq!(total_distance_region(&lone, Taxicab, 2), 9);
+        assert_eq!(total_distance_region(&lone, Chebyshev, 2), 81);
+    }
+
+    #[test]
+    fn site_set() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert_eq!(part1_incremental(&parsed), 17);
+
+        let mut sites = SiteSet::new();
+        for &point in &parsed[..5] {
+            sites.insert(point);
+        }
+
+        // the last point cuts into E's region, which was unbounded until then
+        assert!(sites.is_infinite(4));
+
+        let (id, update) = sites.insert(parsed[5]);
+        assert_eq!(id, 5);
+        assert_eq!(update.largest, Some(4));
+        assert_eq!(sites.size(4), 17);
+        assert!(update.changes.iter().any(|c| c.site == 4 && c.after == 17));
+
+        assert_eq!(sites.remove(9), None);
+
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut sites = SiteSet::new();
+
+        for _ in 0..300 {
+            let live: Vec<_> = sites.sites().collect();
+            let before: Vec<_> = live.iter().map(|&(id, _)| (id, sites.size(id))).collect();
+
+            let update = if live.len() > 3 && rng.gen_range(0, 3) == 0 {
+                let (id, _) = live[rng.gen_range(0, live.len())];
+                sites.remove(id).unwrap()
+            } else {
+                let point = TaxicabSpatial::new(rng.gen_range(-15, 15), rng.gen_range(-15, 15));
+                sites.insert(point).1
+            };
+
+            let (ids, points): (Vec<_>, Vec<_>) = sites.sites().unzip();
+            let report = region_report(&points, Taxicab);
+
+            for (&id, region) in ids.iter().zip(&report.regions) {
+                assert_eq!(sites.size(id), region.size);
+                assert_eq!(sites.is_infinite(id), region.infinite);
+            }
+
+            assert_eq!(update.largest, report.largest.map(|idx| ids[idx]));
+
+            // sites the update added start from zero
+            let expected: Vec<_> = before
+                .iter()
+                .cloned()
+                .chain(ids.iter().map(|&id| (id, 0)))
+                .unique_by(|&(id, _)| id)
+                .filter(|&(id, size)| size != sites.size(id))
+                .sorted()
+                .collect();
+
+            let changes: Vec<_> = update.changes.iter().map(|c| (c.site, c.before)).collect();
+            assert_eq!(changes, expected);
+        }
+    }
 }
@@ -1,34 +1,254 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use hashbrown::HashSet;
+use bimap::BiMap;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use petgraph::prelude::*; // {Graph, graph::NodeIndex, Directed};
 use regex::Regex;
 use std::cmp::{Ordering, Reverse};
-use std::collections::BinaryHeap;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::error::Error;
+use std::fmt;
 
 lazy_static! {
     static ref DEP_RE: Regex = Regex::new(
-        r"Step (?P<dep>[A-Z]) must be finished before step (?P<target>[A-Z]) can begin."
+        r"Step (?P<dep>[\w-]+) must be finished before step (?P<target>[\w-]+) can begin\."
     )
     .unwrap();
+    static ref DURATION_RE: Regex =
+        Regex::new(r"Step (?P<step>[\w-]+) takes (?P<secs>\d+) seconds?\.").unwrap();
 }
 
-#[aoc_generator(day7)]
-pub fn parse_input(input: &str) -> Vec<(u32, u32)> {
-    fn char_idx(c: &str) -> u8 {
-        c.bytes().next().unwrap() - b'A'
+/// The dependencies between steps, with the steps numbered by priority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Steps {
+    /// Step indices to names. When several steps are ready the lowest index goes first.
+    pub names: BiMap<u32, String>,
+    /// `(dependency, step)` index pairs.
+    pub deps: Vec<(u32, u32)>,
+    /// Durations given in the input as `Step X takes N seconds.`, by step index.
+    pub durations: Vec<Option<u32>>,
+}
+
+impl Steps {
+    pub fn name(&self, idx: u32) -> &str {
+        self.names.get_by_left(&idx).unwrap()
+    }
+
+    pub fn index(&self, name: &str) -> Option<u32> {
+        self.names.get_by_right(&name.to_owned()).cloned()
+    }
+
+    /// The step's duration from the input if it has one, otherwise from `step_duration`.
+    pub fn duration(&self, name: &str, offset: u32) -> u32 {
+        self.index(name)
+            .and_then(|idx| self.durations[idx as usize])
+            .unwrap_or_else(|| step_duration(name, offset))
+    }
+}
+
+/// A `Step X takes N seconds.` line whose duration doesn't fit in a `u32`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationError {
+    pub step: String,
+    pub secs: String,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step {}: duration of {} seconds is out of range",
+            self.step, self.secs
+        )
     }
+}
+
+impl Error for DurationError {}
+
+#[aoc_generator(day7)]
+pub fn parse_input(input: &str) -> Result<Steps, DurationError> {
+    parse_input_with(input, |a: &str, b: &str| a.cmp(b))
+}
+
+/// Parses the dependencies and any durations, numbering steps in the priority `order`
+/// gives them, first being the step that should go first when several are ready.
+pub fn parse_input_with<F>(input: &str, mut order: F) -> Result<Steps, DurationError>
+where
+    F: FnMut(&str, &str) -> Ordering,
+{
+    let pairs: Vec<_> = DEP_RE
+        .captures_iter(input)
+        .map(|dep| (dep["dep"].to_owned(), dep["target"].to_owned()))
+        .collect();
 
-    DEP_RE
+    let timed = DURATION_RE
         .captures_iter(input)
-        .map(|dep| {
-            (
-                char_idx(&dep["dep"]) as u32,
-                char_idx(&dep["target"]) as u32,
-            )
+        .map(|step| match step["secs"].parse::<u32>() {
+            Ok(secs) => Ok((step["step"].to_owned(), secs)),
+            Err(_) => Err(DurationError {
+                step: step["step"].to_owned(),
+                secs: step["secs"].to_owned(),
+            }),
         })
-        .collect()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut names: Vec<&str> = pairs
+        .iter()
+        .flat_map(|(dep, target)| vec![dep.as_str(), target.as_str()])
+        .chain(timed.iter().map(|(step, _)| step.as_str()))
+        .collect();
+
+    names.sort_by(|a, b| order(a, b).then_with(|| a.cmp(b)));
+    names.dedup();
+
+    let names: BiMap<u32, String> = names
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| (idx as u32, name.to_owned()))
+        .collect();
+
+    let index = |name: &String| *names.get_by_right(name).unwrap();
+
+    let deps = pairs
+        .iter()
+        .map(|(dep, target)| (index(dep), index(target)))
+        .collect();
+
+    let mut durations = vec![None; names.len()];
+
+    for (step, secs) in &timed {
+        durations[index(step) as usize] = Some(*secs);
+    }
+
+    Ok(Steps {
+        names,
+        deps,
+        durations,
+    })
+}
+
+/// How long a step takes: a single upper case letter takes the puzzle's `offset + 1`
+/// for `A` through `offset + 26` for `Z`, and any other name takes `offset` plus its
+/// length in characters.
+pub fn step_duration(name: &str, offset: u32) -> u32 {
+    match name.as_bytes() {
+        &[c] if c.is_ascii_uppercase() => offset + 1 + u32::from(c - b'A'),
+        _ => offset + name.chars().count() as u32,
+    }
+}
+
+// one node per step that was mentioned, at the step's index
+fn build_graph(inp: &Steps) -> Graph<(), ()> {
+    let mut graph = Graph::with_capacity(inp.names.len(), inp.deps.len());
+
+    for _ in 0..inp.names.len() {
+        graph.add_node(());
+    }
+
+    for &(dep, target) in &inp.deps {
+        graph.add_edge(
+            NodeIndex::new(dep as usize),
+            NodeIndex::new(target as usize),
+            (),
+        );
+    }
+
+    graph
+}
+
+/// A set of dependencies that can never all be satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// Steps around one cycle, each a dependency of the next, with the first repeated
+    /// at the end.
+    pub cycle: Vec<String>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dependency cycle: {}", self.cycle.join(" -> "))
+    }
+}
+
+impl Error for CycleError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    Cycle(CycleError),
+    /// None of the workers are allowed to take this step.
+    Unassignable {
+        step: String,
+    },
+    /// The optimal scheduler tracks steps in a 64 bit mask.
+    TooManySteps {
+        steps: usize,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleError::Cycle(err) => err.fmt(f),
+            ScheduleError::Unassignable { step } => write!(f, "no worker can take step {}", step),
+            ScheduleError::TooManySteps { steps } => {
+                write!(
+                    f,
+                    "{} steps is too many to schedule optimally, 64 at most",
+                    steps
+                )
+            }
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+impl From<CycleError> for ScheduleError {
+    fn from(err: CycleError) -> Self {
+        ScheduleError::Cycle(err)
+    }
+}
+
+// picks a cycle out of the first strongly connected component that has one, always
+// stepping to the lowest numbered successor still in the component
+fn find_cycle(inp: &Steps, graph: &Graph<(), ()>) -> Result<(), CycleError> {
+    use petgraph::algo::kosaraju_scc;
+
+    let component = kosaraju_scc(graph)
+        .into_iter()
+        .filter(|c| c.len() > 1 || graph.contains_edge(c[0], c[0]))
+        .min_by_key(|c| c.iter().min().cloned());
+
+    let component: HashSet<_> = match component {
+        Some(c) => c.into_iter().collect(),
+        None => return Ok(()),
+    };
+
+    let mut path = vec![*component.iter().min().unwrap()];
+
+    loop {
+        let last = *path.last().unwrap();
+        let next = graph
+            .neighbors_directed(last, Outgoing)
+            .filter(|n| component.contains(n))
+            .min()
+            .unwrap();
+
+        if let Some(start) = path.iter().position(|&n| n == next) {
+            path.push(next);
+
+            let cycle = path[start..]
+                .iter()
+                .map(|n| inp.name(n.index() as u32).to_owned())
+                .collect();
+
+            return Err(CycleError { cycle });
+        }
+
+        path.push(next);
+    }
 }
 
 fn get_roots<N, E>(graph: &Graph<N, E, Directed>) -> Vec<NodeIndex> {
@@ -41,9 +261,10 @@ fn get_roots<N, E>(graph: &Graph<N, E, Directed>) -> Vec<NodeIndex> {
         .collect()
 }
 
-#[aoc(day7, part1)]
-pub fn part1(inp: &[(u32, u32)]) -> String {
-    let graph: Graph<(), u32> = Graph::from_edges(inp);
+/// The order the steps get done in by a single worker.
+pub fn step_order(inp: &Steps) -> Result<Vec<String>, CycleError> {
+    let graph = build_graph(inp);
+    find_cycle(inp, &graph)?;
     let mut to_visit: BinaryHeap<Reverse<u32>> = BinaryHeap::new();
 
     let roots = get_roots(&graph);
@@ -75,21 +296,32 @@ pub fn part1(inp: &[(u32, u32)]) -> String {
         }
     }
 
-    visited_order
+    Ok(visited_order
         .iter()
-        .map(|n| (n.0 as u8 + b'A') as char)
-        .collect()
+        .map(|n| inp.name(n.0).to_owned())
+        .collect())
+}
+
+#[aoc(day7, part1)]
+pub fn part1(inp: &Steps) -> Result<String, CycleError> {
+    Ok(step_order(inp)?.concat())
 }
 
-#[derive(Debug, Copy, Clone, Eq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct TimeLeft {
     id: u32,
     left: u32,
+    worker: usize,
+    start: u32,
 }
 
+// the heap pops whatever finishes soonest, then the lowest step id, so the order jobs
+// finishing together are handled in doesn't depend on the heap's internals
 impl Ord for TimeLeft {
     fn cmp(&self, other: &Self) -> Ordering {
-        return Reverse(self.left).cmp(&Reverse(other.left));
+        let key = |t: &Self| Reverse((t.left, t.id, t.worker, t.start));
+
+        key(self).cmp(&key(other))
     }
 }
 
@@ -99,89 +331,582 @@ impl PartialOrd for TimeLeft {
     }
 }
 
-impl PartialEq for TimeLeft {
-    fn eq(&self, other: &Self) -> bool {
-        self.left == other.left
-    }
+/// One step being worked on, from second `start` up to but not including `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledStep {
+    /// Counting from zero.
+    pub worker: usize,
+    pub step: String,
+    pub start: u32,
+    pub end: u32,
 }
 
-impl TimeLeft {
-    fn new<T: Into<u32>>(n: T, offset: u32) -> Self {
-        let n = n.into();
-        TimeLeft {
-            id: n,
-            left: offset + 1 + n,
+#[derive(Debug, Clone)]
+pub struct Worker {
+    /// Work done per second, so a step takes its duration divided by this, rounded up.
+    pub speed: u32,
+    /// The steps this worker may take, or any step when `None`.
+    pub skills: Option<HashSet<String>>,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Worker {
+            speed: 1,
+            skills: None,
         }
     }
 }
 
-fn solve_part2(inp: &[(u32, u32)], num_actors: usize, time_offset: u32) -> u32 {
-    let graph: Graph<(), u32> = Graph::from_edges(inp);
-    let mut to_visit: BinaryHeap<Reverse<u32>> = BinaryHeap::new();
+impl Worker {
+    pub fn can_take(&self, step: &str) -> bool {
+        self.skills.as_ref().is_none_or(|s| s.contains(step))
+    }
+
+    pub fn time_for(&self, duration: u32) -> u32 {
+        duration.div_ceil(self.speed.max(1))
+    }
+}
+
+/// Every step in the order it was started, with the worker that did it, for
+/// `num_actors` identical workers and the durations from `Steps::duration`. With no
+/// workers at all the first ready step is `ScheduleError::Unassignable`.
+pub fn schedule(
+    inp: &Steps,
+    num_actors: usize,
+    time_offset: u32,
+) -> Result<Vec<ScheduledStep>, ScheduleError> {
+    let workers = vec![Worker::default(); num_actors];
+
+    schedule_with(inp, &workers, |name| inp.duration(name, time_offset))
+}
+
+/// Every step in the order it was started, with the worker that did it. Whenever
+/// workers are free the ready steps go out in priority order, each to the lowest
+/// numbered idle worker allowed to take it, and wait if there isn't one.
+///
+/// Steps finishing at the same instant all finish together: their workers go idle and
+/// everything they were holding up becomes ready before any step is handed out.
+pub fn schedule_with<F>(
+    inp: &Steps,
+    workers: &[Worker],
+    duration: F,
+) -> Result<Vec<ScheduledStep>, ScheduleError>
+where
+    F: Fn(&str) -> u32,
+{
+    let graph = build_graph(inp);
+    find_cycle(inp, &graph)?;
+    let mut to_visit: BTreeSet<u32> = get_roots(&graph).iter().map(|n| n.index() as u32).collect();
 
     let mut visiting: BinaryHeap<TimeLeft> = BinaryHeap::new();
     let mut time_taken = 0;
 
-    let roots = get_roots(&graph);
-
-    to_visit.extend(roots.iter().map(|n| Reverse(n.index() as u32)));
+    let mut idle: BTreeSet<usize> = (0..workers.len()).collect();
+    let mut scheduled = Vec::new();
 
-    let mut seen = HashSet::new();
     let mut visited = HashSet::new();
 
-    while let Some(node) = to_visit.pop() {
-        if seen.contains(&node) {
-            continue;
+    loop {
+        for node in to_visit.clone() {
+            let name = inp.name(node);
+
+            let worker = match idle.iter().find(|&&w| workers[w].can_take(name)) {
+                Some(&worker) => worker,
+                None => continue,
+            };
+
+            to_visit.remove(&node);
+            idle.remove(&worker);
+
+            visiting.push(TimeLeft {
+                id: node,
+                left: workers[worker].time_for(duration(name)),
+                worker,
+                start: time_taken,
+            });
         }
 
-        seen.insert(node);
-        visiting.push(TimeLeft::new(node.0, time_offset));
+        let first = match visiting.pop() {
+            Some(job) => job,
+            None => break,
+        };
 
-        while visiting.len() >= num_actors || to_visit.is_empty() && !visiting.is_empty() {
-            let job = visiting.pop().unwrap();
-            time_taken += job.left;
+        time_taken += first.left;
 
-            let nodes: Vec<_> = visiting
-                .drain()
-                .map(|TimeLeft { id, left }| TimeLeft {
-                    id: id,
-                    left: left - job.left,
-                })
-                .collect();
-            visiting.extend(nodes);
+        let nodes: Vec<_> = visiting
+            .drain()
+            .map(|t| TimeLeft {
+                left: t.left - first.left,
+                ..t
+            })
+            .collect();
+        visiting.extend(nodes);
 
-            let job_index = NodeIndex::new(job.id as usize);
+        let mut finished = vec![first];
 
-            visited.insert(job_index);
+        while visiting.peek().is_some_and(|t| t.left == 0) {
+            finished.extend(visiting.pop());
+        }
+
+        for job in &finished {
+            idle.insert(job.worker);
+            visited.insert(NodeIndex::new(job.id as usize));
+
+            scheduled.push(ScheduledStep {
+                worker: job.worker,
+                step: inp.name(job.id).to_owned(),
+                start: job.start,
+                end: time_taken,
+            });
+        }
 
-            for node in graph.neighbors_directed(job_index, Outgoing) {
+        for job in &finished {
+            for node in graph.neighbors_directed(NodeIndex::new(job.id as usize), Outgoing) {
                 let waiting_on: HashSet<_> = graph
                     .neighbors_directed(node, Incoming)
                     .filter(|n| !visited.contains(n))
                     .collect();
 
                 if waiting_on.is_empty() {
-                    to_visit.push(Reverse(node.index() as u32));
+                    to_visit.insert(node.index() as u32);
+                }
+            }
+        }
+    }
+
+    if let Some(&node) = to_visit.iter().next() {
+        return Err(ScheduleError::Unassignable {
+            step: inp.name(node).to_owned(),
+        });
+    }
+
+    scheduled.sort_by_key(|s| (s.start, s.worker));
+
+    Ok(scheduled)
+}
+
+fn solve_part2(inp: &Steps, num_actors: usize, time_offset: u32) -> Result<u32, ScheduleError> {
+    let scheduled = schedule(inp, num_actors, time_offset)?;
+
+    Ok(scheduled.iter().map(|s| s.end).max().unwrap_or(0))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepTiming {
+    pub step: String,
+    pub duration: u32,
+    pub earliest_start: u32,
+    /// The latest the step can start without holding up the whole job.
+    pub latest_start: u32,
+    /// `latest_start - earliest_start`, zero along the critical path.
+    pub slack: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CriticalPath {
+    /// Indexed like the steps.
+    pub timings: Vec<StepTiming>,
+    /// The longest chain of dependent steps by duration, first step first.
+    pub path: Vec<String>,
+    /// The finishing time with as many workers as needed, which is the length of the
+    /// critical path and a lower bound for any number of workers.
+    pub lower_bound: u32,
+}
+
+/// Earliest and latest start times and slack for every step, along with the critical
+/// path through them.
+pub fn critical_path<F>(inp: &Steps, duration: F) -> Result<CriticalPath, CycleError>
+where
+    F: Fn(&str) -> u32,
+{
+    use petgraph::algo::toposort;
+
+    let graph = build_graph(inp);
+    find_cycle(inp, &graph)?;
+
+    let order = toposort(&graph, None).unwrap();
+    let durations: Vec<_> = (0..inp.names.len() as u32)
+        .map(|idx| duration(inp.name(idx)))
+        .collect();
+
+    let mut earliest = vec![0; durations.len()];
+
+    for &node in &order {
+        earliest[node.index()] = graph
+            .neighbors_directed(node, Incoming)
+            .map(|dep| earliest[dep.index()] + durations[dep.index()])
+            .max()
+            .unwrap_or(0);
+    }
+
+    let lower_bound = (0..durations.len())
+        .map(|idx| earliest[idx] + durations[idx])
+        .max()
+        .unwrap_or(0);
+
+    let mut latest = vec![0; durations.len()];
+
+    for &node in order.iter().rev() {
+        let finish_by = graph
+            .neighbors_directed(node, Outgoing)
+            .map(|next| latest[next.index()])
+            .min()
+            .unwrap_or(lower_bound);
+
+        latest[node.index()] = finish_by - durations[node.index()];
+    }
+
+    let timings: Vec<_> = (0..durations.len())
+        .map(|idx| StepTiming {
+            step: inp.name(idx as u32).to_owned(),
+            duration: durations[idx],
+            earliest_start: earliest[idx],
+            latest_start: latest[idx],
+            slack: latest[idx] - earliest[idx],
+        })
+        .collect();
+
+    // follow steps with no slack, each starting as its predecessor finishes
+    let mut path = Vec::new();
+    let mut next = (0..durations.len()).find(|&idx| earliest[idx] == 0 && timings[idx].slack == 0);
+
+    while let Some(idx) = next {
+        path.push(timings[idx].step.clone());
+
+        let finish = earliest[idx] + durations[idx];
+
+        next = graph
+            .neighbors_directed(NodeIndex::new(idx), Outgoing)
+            .map(|n| n.index())
+            .filter(|&n| earliest[n] == finish && timings[n].slack == 0)
+            .min();
+    }
+
+    Ok(CriticalPath {
+        timings,
+        path,
+        lower_bound,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimalSchedule {
+    pub schedule: Vec<ScheduledStep>,
+    pub makespan: u32,
+    /// Where the greedy rule of `schedule_with` finishes on the same input.
+    pub greedy: u32,
+}
+
+impl OptimalSchedule {
+    /// How much sooner the optimal schedule finishes than the greedy one.
+    pub fn gap(&self) -> u32 {
+        self.greedy - self.makespan
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Running {
+    step: u32,
+    worker: usize,
+    end: u32,
+}
+
+struct BranchAndBound {
+    durations: Vec<u32>,
+    // masks of each step's dependencies
+    deps: Vec<u64>,
+    // each step's duration plus the longest chain of steps waiting on it
+    tails: Vec<u32>,
+    workers: usize,
+    best: u32,
+    best_path: Vec<Running>,
+    // the earliest each combination of finished and running steps has been reached
+    seen: HashMap<(u64, Vec<(u32, u32)>), u32>,
+}
+
+impl BranchAndBound {
+    fn started(&self, done: u64, running: &[Running]) -> u64 {
+        running.iter().fold(done, |mask, r| mask | 1 << r.step)
+    }
+
+    // no schedule from here can beat the longest remaining chain of steps, or the
+    // remaining work spread perfectly across the workers
+    fn lower_bound(&self, time: u32, done: u64, running: &[Running]) -> u32 {
+        let started = self.started(done, running);
+
+        let mut bound = time;
+        let mut work = 0;
+
+        for r in running {
+            let step = r.step as usize;
+
+            bound = bound.max(r.end + self.tails[step] - self.durations[step]);
+            work += r.end - time;
+        }
+
+        for step in (0..self.durations.len()).filter(|&s| started & 1 << s == 0) {
+            bound = bound.max(time + self.tails[step]);
+            work += self.durations[step];
+        }
+
+        bound.max(time + work.div_ceil(self.workers as u32))
+    }
+
+    fn search(&mut self, time: u32, done: u64, running: Vec<Running>, path: &mut Vec<Running>) {
+        let steps = self.durations.len();
+
+        if done.count_ones() as usize == steps {
+            if time < self.best {
+                self.best = time;
+                self.best_path = path.clone();
+            }
+
+            return;
+        }
+
+        if self.lower_bound(time, done, &running) >= self.best {
+            return;
+        }
+
+        let key: Vec<_> = running
+            .iter()
+            .map(|r| (r.step, r.end - time))
+            .sorted()
+            .collect();
+        let key = (done, key);
+
+        if self.seen.get(&key).is_some_and(|&t| t <= time) {
+            return;
+        }
+
+        self.seen.insert(key, time);
+
+        let started = self.started(done, &running);
+
+        let ready: Vec<u32> = (0..steps as u32)
+            .filter(|&s| started & 1 << s == 0 && self.deps[s as usize] & !done == 0)
+            .collect();
+
+        let idle: Vec<usize> = (0..self.workers)
+            .filter(|&w| running.iter().all(|r| r.worker != w))
+            .collect();
+
+        // starting as much as possible first finds good schedules early, but holding a
+        // step back for a later one is sometimes what it takes to be optimal
+        for count in (0..=idle.len().min(ready.len())).rev() {
+            if count == 0 && running.is_empty() {
+                continue;
+            }
+
+            // itertools has nothing to combine from an empty pool, not even nothing
+            let choices: Vec<Vec<&u32>> = if count == 0 {
+                vec![Vec::new()]
+            } else {
+                ready.iter().combinations(count).collect()
+            };
+
+            for chosen in choices {
+                let mut next = running.clone();
+
+                for (&step, &worker) in chosen.into_iter().zip(&idle) {
+                    let job = Running {
+                        step,
+                        worker,
+                        end: time + self.durations[step as usize],
+                    };
+
+                    next.push(job);
+                    path.push(job);
                 }
+
+                let next_time = next.iter().map(|r| r.end).min().unwrap();
+
+                let finished = next
+                    .iter()
+                    .filter(|r| r.end == next_time)
+                    .fold(done, |mask, r| mask | 1 << r.step);
+
+                next.retain(|r| r.end != next_time);
+
+                self.search(next_time, finished, next, path);
+
+                path.truncate(path.len() - count);
             }
         }
     }
+}
+
+/// The soonest `num_actors` identical workers can finish, found by branch and bound,
+/// along with how it compares to the greedy schedule.
+///
+/// Steps only ever start when the work starts or as another step finishes, as any
+/// schedule can have its steps pulled earlier until they do without finishing later.
+/// Schedules are cut off once the critical path or the remaining work shows they can't
+/// beat the best found so far, starting from the greedy schedule. The search is still
+/// exponential, so it's meant for graphs of a dozen or so steps rather than the puzzle
+/// input.
+pub fn optimal_schedule<F>(
+    inp: &Steps,
+    num_actors: usize,
+    duration: F,
+) -> Result<OptimalSchedule, ScheduleError>
+where
+    F: Fn(&str) -> u32,
+{
+    let steps = inp.names.len();
+
+    if steps > 64 {
+        return Err(ScheduleError::TooManySteps { steps });
+    }
+
+    let workers = vec![Worker::default(); num_actors];
+    let greedy_schedule = schedule_with(inp, &workers, &duration)?;
+    let greedy = greedy_schedule.iter().map(|s| s.end).max().unwrap_or(0);
+
+    let analysis = critical_path(inp, &duration)?;
+
+    let mut deps = vec![0u64; steps];
+
+    for &(dep, target) in &inp.deps {
+        deps[target as usize] |= 1 << dep;
+    }
+
+    let mut search = BranchAndBound {
+        durations: analysis.timings.iter().map(|t| t.duration).collect(),
+        deps,
+        tails: analysis
+            .timings
+            .iter()
+            .map(|t| analysis.lower_bound - t.latest_start)
+            .collect(),
+        workers: num_actors,
+        best: greedy,
+        best_path: Vec::new(),
+        seen: HashMap::new(),
+    };
+
+    if steps > 0 {
+        search.search(0, 0, Vec::new(), &mut Vec::new());
+    }
+
+    let schedule = if search.best < greedy {
+        search
+            .best_path
+            .iter()
+            .map(|r| ScheduledStep {
+                worker: r.worker,
+                step: inp.name(r.step).to_owned(),
+                start: r.end - search.durations[r.step as usize],
+                end: r.end,
+            })
+            .sorted_by_key(|s| (s.start, s.worker))
+            .collect()
+    } else {
+        greedy_schedule
+    };
 
-    time_taken
+    Ok(OptimalSchedule {
+        schedule,
+        makespan: search.best,
+        greedy,
+    })
+}
+
+/// The puzzle's second by second table of what each worker is doing, with `.` for an
+/// idle worker and the finished steps in the order they finished.
+pub fn render_schedule(scheduled: &[ScheduledStep], num_actors: usize) -> String {
+    let end = scheduled.iter().map(|s| s.end).max().unwrap_or(0);
+
+    let finished: Vec<_> = scheduled
+        .iter()
+        .sorted_by_key(|s| (s.end, s.worker))
+        .collect();
+
+    // single letters run together like the puzzle's, longer names get spaced out
+    let separator = if scheduled.iter().all(|s| s.step.chars().count() == 1) {
+        ""
+    } else {
+        " "
+    };
+
+    let mut header = vec!["Second".to_owned()];
+    header.extend((1..=num_actors).map(|w| format!("Worker {}", w)));
+
+    // widths in characters, which is what the `{:^}` padding counts
+    let name_width = scheduled
+        .iter()
+        .map(|s| s.step.chars().count())
+        .max()
+        .unwrap_or(1);
+    let widths: Vec<_> = header
+        .iter()
+        .map(|h| h.chars().count().max(name_width))
+        .collect();
+
+    let mut out = String::new();
+
+    let mut push_row = |cells: &[String], done: &str| {
+        let row = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:^width$}", cell, width = width))
+            .join("   ");
+
+        out.push_str(format!("{}   {}", row, done).trim_end());
+        out.push('\n');
+    };
+
+    push_row(&header, "Done");
+
+    for second in 0..=end {
+        let mut cells = vec![second.to_string()];
+        cells.extend((0..num_actors).map(|worker| {
+            scheduled
+                .iter()
+                .find(|s| s.worker == worker && s.start <= second && second < s.end)
+                .map_or(".".to_owned(), |s| s.step.clone())
+        }));
+
+        let done = finished
+            .iter()
+            .take_while(|s| s.end <= second)
+            .map(|s| s.step.as_str())
+            .join(separator);
+
+        push_row(&cells, &done);
+    }
+
+    out
+}
+
+pub fn schedule_to_csv(scheduled: &[ScheduledStep]) -> String {
+    let mut out = String::from("worker,step,start,end\n");
+
+    for s in scheduled {
+        out.push_str(&format!("{},{},{},{}\n", s.worker, s.step, s.start, s.end));
+    }
+
+    out
 }
 
 #[aoc(day7, part2)]
-pub fn part2(inp: &[(u32, u32)]) -> u32 {
+pub fn part2(inp: &Steps) -> Result<u32, ScheduleError> {
     solve_part2(inp, 5, 60)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_input, part1, solve_part2};
+    use super::{
+        critical_path, optimal_schedule, parse_input, parse_input_with, part1, part2,
+        render_schedule, schedule, schedule_to_csv, schedule_with, solve_part2, step_duration,
+        step_order, CycleError, DurationError, ScheduleError, ScheduledStep, Worker,
+    };
+    use hashbrown::HashMap;
+    use rand::prng::XorShiftRng;
+    use rand::{Rng, SeedableRng};
 
-    #[test]
-    fn t1() {
-        let inp = r#"Step C must be finished before step A can begin.
+    const INPUT: &str = r#"Step C must be finished before step A can begin.
 Step C must be finished before step F can begin.
 Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
@@ -189,27 +914,503 @@ Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin."#;
 
-        let parsed = parse_input(inp);
+    fn summary(scheduled: &[ScheduledStep]) -> Vec<(usize, &str, u32, u32)> {
+        scheduled
+            .iter()
+            .map(|s| (s.worker, s.step.as_str(), s.start, s.end))
+            .collect()
+    }
+
+    #[test]
+    fn t1() {
+        let parsed = parse_input(INPUT).unwrap();
 
-        let result = part1(&parsed);
+        let result = part1(&parsed).unwrap();
 
         assert_eq!(result, "CABDFE");
     }
 
     #[test]
     fn t2() {
-        let inp = r#"Step C must be finished before step A can begin.
-Step C must be finished before step F can begin.
-Step A must be finished before step B can begin.
-Step A must be finished before step D can begin.
-Step B must be finished before step E can begin.
-Step D must be finished before step E can begin.
-Step F must be finished before step E can begin."#;
+        let parsed = parse_input(INPUT).unwrap();
 
-        let parsed = parse_input(inp);
-
-        let result = solve_part2(&parsed, 2, 0);
+        let result = solve_part2(&parsed, 2, 0).unwrap();
 
         assert_eq!(result, 15);
     }
+
+    #[test]
+    fn named_steps() {
+        let inp = r#"Step fetch must be finished before step compile-core can begin.
+Step compile-core must be finished before step test_all can begin.
+Step fetch must be finished before step lint can begin.
+Step lint must be finished before step test_all can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        assert_eq!(parsed.index("lint"), Some(2));
+        assert_eq!(parsed.name(3), "test_all");
+        assert_eq!(
+            step_order(&parsed).unwrap(),
+            vec!["fetch", "compile-core", "lint", "test_all"]
+        );
+
+        // fetch, then compile-core and lint side by side
+        assert_eq!(solve_part2(&parsed, 2, 0).unwrap(), 5 + 12 + 8);
+
+        let reversed = parse_input_with(inp, |a, b| b.cmp(a)).unwrap();
+
+        assert_eq!(
+            step_order(&reversed).unwrap(),
+            vec!["fetch", "lint", "compile-core", "test_all"]
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(step_duration("A", 60), 61);
+        assert_eq!(step_duration("Z", 0), 26);
+        assert_eq!(step_duration("a", 0), 1);
+        assert_eq!(step_duration("test_all", 10), 18);
+    }
+
+    #[test]
+    fn alphabet_gap() {
+        let inp = r#"Step A must be finished before step C can begin.
+Step C must be finished before step E can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        assert_eq!(part1(&parsed).unwrap(), "ACE");
+        assert_eq!(solve_part2(&parsed, 2, 0).unwrap(), 1 + 3 + 5);
+    }
+
+    #[test]
+    fn cycle() {
+        let inp = r#"Step A must be finished before step C can begin.
+Step C must be finished before step B can begin.
+Step B must be finished before step A can begin.
+Step D must be finished before step A can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        let err = step_order(&parsed).unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle: A -> C -> B -> A");
+        assert_eq!(part1(&parsed), Err(err.clone()));
+        assert_eq!(part2(&parsed), Err(ScheduleError::Cycle(err.clone())));
+        assert_eq!(solve_part2(&parsed, 2, 0), Err(ScheduleError::Cycle(err)));
+
+        let parsed = parse_input("Step A must be finished before step A can begin.").unwrap();
+
+        assert_eq!(
+            step_order(&parsed),
+            Err(CycleError {
+                cycle: vec!["A".to_owned(), "A".to_owned()],
+            })
+        );
+    }
+
+    #[test]
+    fn timeline() {
+        let parsed = parse_input(INPUT).unwrap();
+        let scheduled = schedule(&parsed, 2, 0).unwrap();
+
+        assert_eq!(
+            scheduled[2],
+            ScheduledStep {
+                worker: 1,
+                step: "F".to_owned(),
+                start: 3,
+                end: 9,
+            }
+        );
+
+        let table = render_schedule(&scheduled, 2);
+        let lines: Vec<_> = table.lines().collect();
+
+        assert_eq!(lines.len(), 17);
+        assert_eq!(lines[0], "Second   Worker 1   Worker 2   Done");
+        assert_eq!(lines[1], "  0         C          .");
+        assert_eq!(lines[4], "  3         A          F       C");
+        assert_eq!(lines[16], "  15        .          .       CABFDE");
+
+        assert_eq!(
+            schedule_to_csv(&scheduled),
+            "worker,step,start,end
+0,C,0,3
+0,A,3,4
+1,F,3,9
+0,B,4,6
+0,D,6,10
+0,E,10,15
+"
+        );
+    }
+
+    #[test]
+    fn wide_names() {
+        let inp = r#"Step résumé must be finished before step déploiement can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+        let scheduled = schedule(&parsed, 1, 0).unwrap();
+
+        let table = render_schedule(&scheduled, 1);
+        let lines: Vec<_> = table.lines().collect();
+
+        // columns are as wide as the longest name in characters, not bytes
+        assert_eq!(lines.len(), 19);
+        assert_eq!(lines[0], "  Second       Worker 1     Done");
+        assert_eq!(lines[1], "     0          résumé");
+        assert_eq!(lines[7], "     6        déploiement   résumé");
+        assert_eq!(lines[18], "    17             .        résumé déploiement");
+    }
+
+    #[test]
+    fn duration_table() {
+        let inp = r#"Step A must be finished before step B can begin.
+Step A takes 7 seconds.
+Step Z takes 2 seconds."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        assert_eq!(parsed.durations, vec![Some(7), None, Some(2)]);
+        assert_eq!(step_order(&parsed).unwrap(), vec!["A", "B", "Z"]);
+        assert_eq!(solve_part2(&parsed, 1, 0).unwrap(), 7 + 2 + 2);
+        assert_eq!(solve_part2(&parsed, 2, 0).unwrap(), 7 + 2);
+
+        let workers = vec![Worker::default(); 2];
+        let scheduled = schedule_with(&parsed, &workers, |_| 3).unwrap();
+
+        assert_eq!(scheduled.iter().map(|s| s.end).max(), Some(6));
+    }
+
+    #[test]
+    fn duration_out_of_range() {
+        let inp = r#"Step A must be finished before step B can begin.
+Step X takes 99999999999 seconds."#;
+
+        let err = parse_input(inp).unwrap_err();
+
+        assert_eq!(
+            err,
+            DurationError {
+                step: "X".to_owned(),
+                secs: "99999999999".to_owned(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "step X: duration of 99999999999 seconds is out of range"
+        );
+    }
+
+    #[test]
+    fn heterogeneous_workers() {
+        let inp = r#"Step fetch must be finished before step compile-core can begin.
+Step compile-core must be finished before step test_all can begin.
+Step fetch must be finished before step lint can begin.
+Step lint must be finished before step test_all can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        let workers = vec![
+            Worker {
+                speed: 2,
+                skills: None,
+            },
+            Worker {
+                speed: 1,
+                skills: Some(vec!["lint".to_owned()].into_iter().collect()),
+            },
+        ];
+
+        let scheduled = schedule_with(&parsed, &workers, |name| step_duration(name, 0)).unwrap();
+
+        assert_eq!(
+            summary(&scheduled),
+            vec![
+                (0, "fetch", 0, 3),
+                (0, "compile-core", 3, 9),
+                (1, "lint", 3, 7),
+                (0, "test_all", 9, 13),
+            ]
+        );
+
+        let linter = vec![workers[1].clone()];
+
+        assert_eq!(
+            schedule_with(&parsed, &linter, |_| 1),
+            Err(ScheduleError::Unassignable {
+                step: "fetch".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn no_workers() {
+        let parsed = parse_input("Step C must be finished before step A can begin.").unwrap();
+
+        let err = ScheduleError::Unassignable {
+            step: "C".to_owned(),
+        };
+
+        assert_eq!(schedule(&parsed, 0, 0), Err(err.clone()));
+        assert_eq!(solve_part2(&parsed, 0, 0), Err(err));
+    }
+
+    #[test]
+    fn simultaneous_finish() {
+        let inp = r#"Step A must be finished before step Y can begin.
+Step B must be finished before step X can begin."#;
+
+        let parsed = parse_input(inp).unwrap();
+        let workers = vec![Worker::default(); 2];
+
+        // A and B finish together, so X and Y are both ready before either worker is
+        // handed a new step and X goes first, even though A is handled before B
+        let scheduled = schedule_with(&parsed, &workers, |_| 1).unwrap();
+
+        assert_eq!(
+            summary(&scheduled),
+            vec![
+                (0, "A", 0, 1),
+                (1, "B", 0, 1),
+                (0, "X", 1, 2),
+                (1, "Y", 1, 2)
+            ]
+        );
+
+        let inp = r#"Step P must be finished before step B can begin.
+Step Q must be finished before step A can begin.
+Step A must be finished before step X can begin.
+Step B must be finished before step Y can begin.
+Step C must be finished before step Z can begin.
+Step P takes 1 second.
+Step Q takes 2 seconds.
+Step X takes 1 second.
+Step Y takes 1 second.
+Step Z takes 1 second."#;
+
+        let parsed = parse_input(inp).unwrap();
+        let workers = vec![Worker::default(); 3];
+
+        // C, B and A finish together on workers 0, 1 and 2, so they pop in the opposite
+        // order to their workers. Handing X to the first freed worker would give it to
+        // worker 2, but all three are idle by the time X, Y and Z go out.
+        let scheduled = schedule_with(&parsed, &workers, |name| parsed.duration(name, 0)).unwrap();
+
+        assert_eq!(
+            summary(&scheduled),
+            vec![
+                (0, "C", 0, 3),
+                (1, "P", 0, 1),
+                (2, "Q", 0, 2),
+                (1, "B", 1, 3),
+                (2, "A", 2, 3),
+                (0, "X", 3, 4),
+                (1, "Y", 3, 4),
+                (2, "Z", 3, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn critical() {
+        let parsed = parse_input(INPUT).unwrap();
+        let analysis = critical_path(&parsed, |name| parsed.duration(name, 0)).unwrap();
+
+        assert_eq!(analysis.path, vec!["C", "F", "E"]);
+        assert_eq!(analysis.lower_bound, 14);
+
+        let timings: Vec<_> = analysis
+            .timings
+            .iter()
+            .map(|t| (t.step.as_str(), t.earliest_start, t.latest_start, t.slack))
+            .collect();
+
+        assert_eq!(
+            timings,
+            vec![
+                ("A", 3, 4, 1),
+                ("B", 4, 7, 3),
+                ("C", 0, 0, 0),
+                ("D", 4, 5, 1),
+                ("E", 9, 9, 0),
+                ("F", 3, 3, 0),
+            ]
+        );
+
+        // two workers can't get down to the bound here
+        assert_eq!(solve_part2(&parsed, 2, 0).unwrap(), 15);
+        assert_eq!(solve_part2(&parsed, 6, 0).unwrap(), 14);
+    }
+
+    #[test]
+    fn optimal() {
+        let inp = r#"Step A takes 1 second.
+Step B takes 1 second.
+Step Z takes 3 seconds."#;
+
+        let parsed = parse_input(inp).unwrap();
+
+        // greedy starts A and B before the long Z
+        let result = optimal_schedule(&parsed, 2, |name| parsed.duration(name, 0)).unwrap();
+
+        assert_eq!((result.greedy, result.makespan, result.gap()), (4, 3, 1));
+        assert_eq!(result.schedule.len(), 3);
+
+        let parsed = parse_input(INPUT).unwrap();
+        let result = optimal_schedule(&parsed, 2, |name| parsed.duration(name, 0)).unwrap();
+
+        assert_eq!((result.greedy, result.makespan), (15, 15));
+        assert_eq!(result.schedule, schedule(&parsed, 2, 0).unwrap());
+    }
+
+    // the shortest makespan over every schedule on whole seconds, where any set of ready
+    // steps the free workers can cover may start each second, given each step's duration
+    // and the steps it depends on as a mask
+    fn exhaustive_makespan(durations: &[u32], deps: &[u64], workers: usize) -> u32 {
+        // seconds left and step for everything being worked on, sorted
+        type Running = Vec<(u32, usize)>;
+
+        fn search(
+            durations: &[u32],
+            deps: &[u64],
+            workers: usize,
+            done: u64,
+            running: Running,
+            seen: &mut HashMap<(u64, Running), u32>,
+        ) -> u32 {
+            if done.count_ones() as usize == durations.len() {
+                return 0;
+            }
+
+            if let Some(&best) = seen.get(&(done, running.clone())) {
+                return best;
+            }
+
+            let busy = running
+                .iter()
+                .fold(done, |mask, &(_, step)| mask | 1 << step);
+            let ready: Vec<_> = (0..durations.len())
+                .filter(|&step| busy & 1 << step == 0 && deps[step] & !done == 0)
+                .collect();
+
+            let mut best = u32::MAX;
+
+            for subset in 0..1u32 << ready.len() {
+                if subset.count_ones() as usize > workers - running.len() {
+                    continue;
+                }
+
+                let mut next = running.clone();
+                next.extend(
+                    ready
+                        .iter()
+                        .enumerate()
+                        .filter(|&(bit, _)| subset & 1 << bit != 0)
+                        .map(|(_, &step)| (durations[step], step)),
+                );
+
+                // waiting with nothing running gets nowhere
+                if next.is_empty() {
+                    continue;
+                }
+
+                let mut finished = done;
+
+                for job in &mut next {
+                    job.0 -= 1;
+
+                    if job.0 == 0 {
+                        finished |= 1 << job.1;
+                    }
+                }
+
+                next.retain(|&(left, _)| left > 0);
+                next.sort();
+
+                best = best.min(1 + search(durations, deps, workers, finished, next, seen));
+            }
+
+            seen.insert((done, running), best);
+            best
+        }
+
+        search(durations, deps, workers, 0, Vec::new(), &mut HashMap::new())
+    }
+
+    #[test]
+    fn optimal_random() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+
+        for _ in 0..40 {
+            let steps = rng.gen_range(3, 9);
+            let mut inp = String::new();
+
+            for step in 0..steps {
+                let name = (b'A' + step as u8) as char;
+                inp += &format!("Step {} takes {} seconds.\n", name, rng.gen_range(1, 7));
+
+                for dep in 0..step {
+                    if rng.gen_range(0, 4) == 0 {
+                        let dep = (b'A' + dep as u8) as char;
+                        inp += &format!(
+                            "Step {} must be finished before step {} can begin.\n",
+                            dep, name
+                        );
+                    }
+                }
+            }
+
+            let parsed = parse_input(&inp).unwrap();
+            let duration = |name: &str| parsed.duration(name, 0);
+            let workers = rng.gen_range(1, 4);
+
+            let result = optimal_schedule(&parsed, workers, duration).unwrap();
+            let bound = critical_path(&parsed, duration).unwrap().lower_bound;
+
+            assert!(bound <= result.makespan && result.makespan <= result.greedy);
+
+            let durations: Vec<_> = (0..steps)
+                .map(|step| duration(parsed.name(step as u32)))
+                .collect();
+            let mut deps = vec![0; steps];
+
+            for &(dep, target) in &parsed.deps {
+                deps[target as usize] |= 1 << dep;
+            }
+
+            assert_eq!(
+                result.makespan,
+                exhaustive_makespan(&durations, &deps, workers)
+            );
+            assert_eq!(
+                result.schedule.iter().map(|s| s.end).max(),
+                Some(result.makespan)
+            );
+
+            let ends: HashMap<_, _> = result
+                .schedule
+                .iter()
+                .map(|s| (s.step.as_str(), s.end))
+                .collect();
+
+            for s in &result.schedule {
+                assert_eq!(s.end - s.start, duration(&s.step));
+
+                let idx = parsed.index(&s.step).unwrap();
+                for &(dep, _) in parsed.deps.iter().filter(|&&(_, t)| t == idx) {
+                    assert!(ends[parsed.name(dep)] <= s.start);
+                }
+
+                let overlapping = result
+                    .schedule
+                    .iter()
+                    .filter(|o| o.worker == s.worker && o.start < s.end && s.start < o.end)
+                    .count();
+                assert_eq!(overlapping, 1);
+            }
+        }
+    }
 }